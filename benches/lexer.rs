@@ -0,0 +1,89 @@
+//! Compares lexing a big generated script into owned `Token`s and into `BorrowedToken`s
+//!
+//! Run with `cargo bench --bench lexer`. Every allocation goes through a counting allocator,
+//! so as well as timings this shows how many allocations each path makes.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use lox::Lexer;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const RUNS: usize = 5;
+
+/// A few megabytes of the kind of code our generators spit out
+fn script() -> String {
+    let mut source = String::new();
+    for i in 0..20_000 {
+        source.push_str(&format!(
+            "/// Entry {i}\n\
+             fun entry_{i}(value, scale) {{\n    \
+                 var label = \"entry \\t{i}\";\n    \
+                 if (value > 0x{i:X} and scale != 1_000) {{\n        \
+                     return value * scale + 6.02e23; // big\n    \
+                 }}\n    \
+                 return label + value;\n\
+             }}\n",
+            i = i
+        ));
+    }
+    source
+}
+
+/// Lex with `scan` a few times, printing the fastest run and the allocations it made
+fn measure(name: &str, mut scan: impl FnMut() -> usize) {
+    let mut fastest = Duration::MAX;
+    let mut tokens = 0;
+    let mut allocations = 0;
+    let mut bytes = 0;
+    for _ in 0..RUNS {
+        ALLOCATIONS.store(0, Ordering::Relaxed);
+        BYTES.store(0, Ordering::Relaxed);
+        let start = Instant::now();
+        tokens = scan();
+        fastest = fastest.min(start.elapsed());
+        allocations = ALLOCATIONS.load(Ordering::Relaxed);
+        bytes = BYTES.load(Ordering::Relaxed);
+    }
+    println!(
+        "{:<14} {:>9} tokens {:>9} allocations {:>11} bytes {:>9.2?}",
+        name, tokens, allocations, bytes, fastest
+    );
+}
+
+fn main() {
+    let lexer = Lexer::new(script());
+
+    measure("scan_tokens", || {
+        lexer.scan_tokens().filter(Result::is_ok).count()
+    });
+    measure("scan_borrowed", || {
+        lexer.scan_borrowed().filter(Result::is_ok).count()
+    });
+}
@@ -6,20 +6,68 @@ pub mod visitor;
 
 // ast.rs
 
-use crate::lex::Token;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::lex::{escape_string, Token};
+
+/// Identifies an expression that refers to a variable
+///
+/// The resolver records how many scopes away each variable lives using these ids,
+/// they are unique for the lifetime of the program so that REPL lines never collide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(usize);
+
+impl ExprId {
+    pub fn fresh() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        ExprId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// A value as written in source code
+///
+/// This is purely syntax, the evaluator converts it into an `evaluate::Value` at runtime.
 #[derive(Clone, Debug, PartialEq)]
 pub enum LiteralValue {
-    Number(f32),
+    Number(f64),
     String(String),
     Bool(bool),
     Nil,
 }
 
+/// Formats a number the way `print` shows it
+///
+/// Integers have no trailing `.0` and every other number is the shortest text that parses back
+/// to exactly the same value. Very large and very small numbers use an exponent, like `1e21`.
+/// Dividing by zero gives `inf`, `-inf` or `nan`.
+pub fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        "nan".to_string()
+    } else if n.is_infinite() {
+        if n > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else if n != 0.0 && (n.abs() >= 1e21 || n.abs() < 1e-6) {
+        format!("{:e}", n)
+    } else {
+        n.to_string()
+    }
+}
+
+impl std::fmt::Display for LiteralValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LiteralValue::Number(n) => write!(f, "{}", format_number(*n)),
+            LiteralValue::String(s) => write!(f, "{}", escape_string(s)),
+            LiteralValue::Bool(true) => write!(f, "true"),
+            LiteralValue::Bool(false) => write!(f, "false"),
+            LiteralValue::Nil => write!(f, "nil"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Ast {
-    Expr(Expr),
-    Stmt(Stmt),
+    Expr(Box<Expr>),
+    Stmt(Box<Stmt>),
 }
 
 #[derive(Debug, Clone)]
@@ -28,6 +76,15 @@ pub enum Expr {
     Binary(Binary),
     Unary(Unary),
     Literal(LiteralValue),
+    Variable(Variable),
+    Assign(Assign),
+    Logical(Logical),
+    Call(Call),
+    Get(Get),
+    Set(Set),
+    This(This),
+    Super(Super),
+    Interpolation(Interpolation),
 }
 
 impl Expr {
@@ -57,6 +114,68 @@ impl Expr {
     pub fn grouping(expr: Expr) -> Self {
         Expr::Grouping(Grouping(Box::new(expr)))
     }
+
+    pub fn variable(name: Token) -> Self {
+        Expr::Variable(Variable::new(name))
+    }
+
+    pub fn assign(name: Token, value: Expr) -> Self {
+        Expr::Assign(Assign {
+            name,
+            value: Box::new(value),
+            id: ExprId::fresh(),
+        })
+    }
+
+    pub fn call(callee: Expr, paren: Token, arguments: Vec<Expr>) -> Self {
+        Expr::Call(Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        })
+    }
+
+    pub fn get(object: Expr, name: Token) -> Self {
+        Expr::Get(Get {
+            object: Box::new(object),
+            name,
+        })
+    }
+
+    pub fn set(object: Expr, name: Token, value: Expr) -> Self {
+        Expr::Set(Set {
+            object: Box::new(object),
+            name,
+            value: Box::new(value),
+        })
+    }
+
+    pub fn this(keyword: Token) -> Self {
+        Expr::This(This {
+            keyword,
+            id: ExprId::fresh(),
+        })
+    }
+
+    pub fn super_method(keyword: Token, method: Token) -> Self {
+        Expr::Super(Super {
+            keyword,
+            method,
+            id: ExprId::fresh(),
+        })
+    }
+
+    pub fn logical(left: Expr, operator: Token, right: Expr) -> Self {
+        Expr::Logical(Logical {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        })
+    }
+
+    pub fn interpolation(parts: Vec<Expr>) -> Self {
+        Expr::Interpolation(Interpolation { parts })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -78,10 +197,95 @@ pub struct Unary {
 #[derive(Debug, Clone)]
 pub struct Literal(pub LiteralValue);
 
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub name: Token,
+    pub id: ExprId,
+}
+
+impl Variable {
+    pub fn new(name: Token) -> Self {
+        Variable {
+            name,
+            id: ExprId::fresh(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Assign {
+    pub name: Token,
+    pub value: Box<Expr>,
+    pub id: ExprId,
+}
+
+/// `and` and `or` are kept apart from `Binary` because they short-circuit
+#[derive(Debug, Clone)]
+pub struct Logical {
+    pub left: Box<Expr>,
+    pub operator: Token,
+    pub right: Box<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub callee: Box<Expr>,
+    /// The closing parenthesis, used to report errors at the call site
+    pub paren: Token,
+    pub arguments: Vec<Expr>,
+}
+
+/// Property access, e.g. `object.name`
+#[derive(Debug, Clone)]
+pub struct Get {
+    pub object: Box<Expr>,
+    pub name: Token,
+}
+
+/// Property assignment, e.g. `object.name = value`
+#[derive(Debug, Clone)]
+pub struct Set {
+    pub object: Box<Expr>,
+    pub name: Token,
+    pub value: Box<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct This {
+    pub keyword: Token,
+    pub id: ExprId,
+}
+
+/// A method looked up on the superclass, e.g. `super.method`
+#[derive(Debug, Clone)]
+pub struct Super {
+    pub keyword: Token,
+    pub method: Token,
+    pub id: ExprId,
+}
+
+/// A string with expressions in it, e.g. `"total: ${a + b} items"`
+///
+/// The parts are the string literals and the expressions in the order they're written,
+/// empty literals are left out.
+#[derive(Debug, Clone)]
+pub struct Interpolation {
+    pub parts: Vec<Expr>,
+}
+
 #[derive(Debug, Clone)]
 pub enum Stmt {
     ExprStmt(ExprStmt),
     PrintStmt(PrintStmt),
+    Var(Var),
+    Block(Block),
+    If(If),
+    While(While),
+    Break(Break),
+    Continue(Continue),
+    Function(Function),
+    Return(Return),
+    Class(Class),
 }
 
 #[derive(Debug, Clone)]
@@ -90,6 +294,68 @@ pub struct ExprStmt(pub Expr);
 #[derive(Debug, Clone)]
 pub struct PrintStmt(pub Expr);
 
+#[derive(Debug, Clone)]
+pub struct Var {
+    pub name: Token,
+    pub initializer: Option<Expr>,
+    /// from the `///` comments above the declaration
+    pub doc: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub statements: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone)]
+pub struct If {
+    pub condition: Expr,
+    pub then_branch: Box<Stmt>,
+    pub else_branch: Option<Box<Stmt>>,
+}
+
+/// `for` loops are desugared into a `While` with an `increment`
+///
+/// The increment is kept separate from the body so that `continue` still runs it.
+#[derive(Debug, Clone)]
+pub struct While {
+    pub condition: Expr,
+    pub body: Box<Stmt>,
+    pub increment: Option<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Break {
+    pub keyword: Token,
+}
+
+#[derive(Debug, Clone)]
+pub struct Continue {
+    pub keyword: Token,
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+    pub doc: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Return {
+    pub keyword: Token,
+    pub value: Option<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Class {
+    pub name: Token,
+    pub superclass: Option<Variable>,
+    pub methods: Vec<Function>,
+    pub doc: Option<String>,
+}
+
 impl Stmt {
     pub fn expression_statement(expr: Expr) -> Self {
         Stmt::ExprStmt(ExprStmt(expr))
@@ -98,4 +364,67 @@ impl Stmt {
     pub fn print_statement(expr: Expr) -> Self {
         Stmt::PrintStmt(PrintStmt(expr))
     }
+
+    pub fn var_declaration(name: Token, initializer: Option<Expr>, doc: Option<String>) -> Self {
+        Stmt::Var(Var {
+            name,
+            initializer,
+            doc,
+        })
+    }
+
+    pub fn block(statements: Vec<Stmt>) -> Self {
+        Stmt::Block(Block { statements })
+    }
+
+    pub fn if_statement(condition: Expr, then_branch: Stmt, else_branch: Option<Stmt>) -> Self {
+        Stmt::If(If {
+            condition,
+            then_branch: Box::new(then_branch),
+            else_branch: else_branch.map(Box::new),
+        })
+    }
+
+    pub fn while_statement(condition: Expr, body: Stmt, increment: Option<Expr>) -> Self {
+        Stmt::While(While {
+            condition,
+            body: Box::new(body),
+            increment,
+        })
+    }
+
+    pub fn break_statement(keyword: Token) -> Self {
+        Stmt::Break(Break { keyword })
+    }
+
+    pub fn continue_statement(keyword: Token) -> Self {
+        Stmt::Continue(Continue { keyword })
+    }
+
+    pub fn function(name: Token, params: Vec<Token>, body: Vec<Stmt>, doc: Option<String>) -> Self {
+        Stmt::Function(Function {
+            name,
+            params,
+            body,
+            doc,
+        })
+    }
+
+    pub fn return_statement(keyword: Token, value: Option<Expr>) -> Self {
+        Stmt::Return(Return { keyword, value })
+    }
+
+    pub fn class(
+        name: Token,
+        superclass: Option<Variable>,
+        methods: Vec<Function>,
+        doc: Option<String>,
+    ) -> Self {
+        Stmt::Class(Class {
+            name,
+            superclass,
+            methods,
+            doc,
+        })
+    }
 }
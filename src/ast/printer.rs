@@ -46,6 +46,45 @@ impl Visitor<String> for DebugPrinter {
     fn visit_literal(&self, literal: &LiteralValue) -> String {
         literal.to_string()
     }
+    fn visit_variable(&self, variable: &Variable) -> String {
+        variable.name.name().to_string()
+    }
+    fn visit_logical(&self, logical: &Logical) -> String {
+        self.parenthesize(
+            &logical.operator.lexeme,
+            once(logical.left.as_ref()).chain(once(logical.right.as_ref())),
+        )
+    }
+    fn visit_call(&self, call: &Call) -> String {
+        self.parenthesize(
+            "call",
+            once(call.callee.as_ref()).chain(call.arguments.iter()),
+        )
+    }
+    fn visit_get(&self, get: &Get) -> String {
+        self.parenthesize(format!(". {}", get.name.name()), once(get.object.as_ref()))
+    }
+    fn visit_set(&self, set: &Set) -> String {
+        self.parenthesize(
+            format!("= .{}", set.name.name()),
+            once(set.object.as_ref()).chain(once(set.value.as_ref())),
+        )
+    }
+    fn visit_this(&self, _this: &This) -> String {
+        "this".to_string()
+    }
+    fn visit_super(&self, super_method: &Super) -> String {
+        format!("super.{}", super_method.method.name())
+    }
+    fn visit_interpolation(&self, interpolation: &Interpolation) -> String {
+        self.parenthesize("interpolate", interpolation.parts.iter())
+    }
+    fn visit_assign(&self, assign: &Assign) -> String {
+        self.parenthesize(
+            format!("= {}", assign.name.name()),
+            once(assign.value.as_ref()),
+        )
+    }
 }
 pub struct RpnPrinter;
 
@@ -88,6 +127,45 @@ impl Visitor<String> for RpnPrinter {
     fn visit_literal(&self, literal: &LiteralValue) -> String {
         literal.to_string()
     }
+    fn visit_variable(&self, variable: &Variable) -> String {
+        variable.name.name().to_string()
+    }
+    fn visit_logical(&self, logical: &Logical) -> String {
+        self.parenthesize(
+            &logical.operator.lexeme,
+            once(logical.left.as_ref()).chain(once(logical.right.as_ref())),
+        )
+    }
+    fn visit_call(&self, call: &Call) -> String {
+        self.parenthesize(
+            "call",
+            once(call.callee.as_ref()).chain(call.arguments.iter()),
+        )
+    }
+    fn visit_get(&self, get: &Get) -> String {
+        self.parenthesize(format!(".{}", get.name.name()), once(get.object.as_ref()))
+    }
+    fn visit_set(&self, set: &Set) -> String {
+        self.parenthesize(
+            format!(".{} =", set.name.name()),
+            once(set.object.as_ref()).chain(once(set.value.as_ref())),
+        )
+    }
+    fn visit_this(&self, _this: &This) -> String {
+        "this".to_string()
+    }
+    fn visit_super(&self, super_method: &Super) -> String {
+        format!("super.{}", super_method.method.name())
+    }
+    fn visit_interpolation(&self, interpolation: &Interpolation) -> String {
+        self.parenthesize("interpolate", interpolation.parts.iter())
+    }
+    fn visit_assign(&self, assign: &Assign) -> String {
+        self.parenthesize(
+            format!("{} =", assign.name.name()),
+            once(assign.value.as_ref()),
+        )
+    }
 }
 
 #[cfg(test)]
@@ -106,6 +184,7 @@ mod test {
                     literal: None,
                     lexeme: "-".to_string(),
                     span: Span::new(),
+                    trivia: Vec::new(),
                 },
                 right: Box::new(Expr::Literal(LiteralValue::Number(123.0))),
             })),
@@ -114,6 +193,7 @@ mod test {
                 literal: None,
                 lexeme: "*".to_string(),
                 span: Span::new(),
+                trivia: Vec::new(),
             },
             right: Box::new(Expr::Grouping(Grouping(Box::new(Expr::Literal(
                 LiteralValue::Number(45.67),
@@ -136,6 +216,7 @@ mod test {
                     literal: None,
                     lexeme: "+".to_string(),
                     span: Span::new(),
+                    trivia: Vec::new(),
                 },
                 right: Box::new(Expr::Literal(LiteralValue::Number(2.0))),
             })),
@@ -144,6 +225,7 @@ mod test {
                 literal: None,
                 lexeme: "*".to_string(),
                 span: Span::new(),
+                trivia: Vec::new(),
             },
             right: Box::new(Expr::Binary(Binary {
                 left: Box::new(Expr::Literal(LiteralValue::Number(4.0))),
@@ -152,6 +234,7 @@ mod test {
                     literal: None,
                     lexeme: "-".to_string(),
                     span: Span::new(),
+                    trivia: Vec::new(),
                 },
                 right: Box::new(Expr::Literal(LiteralValue::Number(3.0))),
             })),
@@ -5,12 +5,30 @@ pub trait Visitor<T> {
     fn visit_binary(&self, binary: &Binary) -> T;
     fn visit_unary(&self, unary: &Unary) -> T;
     fn visit_literal(&self, literal_value: &LiteralValue) -> T;
+    fn visit_variable(&self, variable: &Variable) -> T;
+    fn visit_assign(&self, assign: &Assign) -> T;
+    fn visit_logical(&self, logical: &Logical) -> T;
+    fn visit_call(&self, call: &Call) -> T;
+    fn visit_get(&self, get: &Get) -> T;
+    fn visit_set(&self, set: &Set) -> T;
+    fn visit_this(&self, this: &This) -> T;
+    fn visit_super(&self, super_method: &Super) -> T;
+    fn visit_interpolation(&self, interpolation: &Interpolation) -> T;
     fn visit_expr(&self, expr: &Expr) -> T {
         match expr {
             Expr::Grouping(inner) => self.visit_grouping(inner),
             Expr::Binary(inner) => self.visit_binary(inner),
             Expr::Unary(inner) => self.visit_unary(inner),
             Expr::Literal(inner) => self.visit_literal(inner),
+            Expr::Variable(inner) => self.visit_variable(inner),
+            Expr::Assign(inner) => self.visit_assign(inner),
+            Expr::Logical(inner) => self.visit_logical(inner),
+            Expr::Call(inner) => self.visit_call(inner),
+            Expr::Get(inner) => self.visit_get(inner),
+            Expr::Set(inner) => self.visit_set(inner),
+            Expr::This(inner) => self.visit_this(inner),
+            Expr::Super(inner) => self.visit_super(inner),
+            Expr::Interpolation(inner) => self.visit_interpolation(inner),
         }
     }
 }
@@ -20,12 +38,88 @@ pub trait OwnedVisitor<T> {
     fn visit_binary(&self, binary: Binary) -> T;
     fn visit_unary(&self, unary: Unary) -> T;
     fn visit_literal(&self, literal_value: LiteralValue) -> T;
+    fn visit_variable(&self, variable: Variable) -> T;
+    fn visit_assign(&self, assign: Assign) -> T;
+    fn visit_logical(&self, logical: Logical) -> T;
+    fn visit_call(&self, call: Call) -> T;
+    fn visit_get(&self, get: Get) -> T;
+    fn visit_set(&self, set: Set) -> T;
+    fn visit_this(&self, this: This) -> T;
+    fn visit_super(&self, super_method: Super) -> T;
+    fn visit_interpolation(&self, interpolation: Interpolation) -> T;
     fn visit_expr(&self, expr: Expr) -> T {
         match expr {
             Expr::Grouping(inner) => self.visit_grouping(inner),
             Expr::Binary(inner) => self.visit_binary(inner),
             Expr::Unary(inner) => self.visit_unary(inner),
             Expr::Literal(inner) => self.visit_literal(inner),
+            Expr::Variable(inner) => self.visit_variable(inner),
+            Expr::Assign(inner) => self.visit_assign(inner),
+            Expr::Logical(inner) => self.visit_logical(inner),
+            Expr::Call(inner) => self.visit_call(inner),
+            Expr::Get(inner) => self.visit_get(inner),
+            Expr::Set(inner) => self.visit_set(inner),
+            Expr::This(inner) => self.visit_this(inner),
+            Expr::Super(inner) => self.visit_super(inner),
+            Expr::Interpolation(inner) => self.visit_interpolation(inner),
+        }
+    }
+}
+
+pub trait StmtVisitor<T> {
+    fn visit_expr_stmt(&self, stmt: &ExprStmt) -> T;
+    fn visit_print_stmt(&self, stmt: &PrintStmt) -> T;
+    fn visit_var_stmt(&self, stmt: &Var) -> T;
+    fn visit_block(&self, block: &Block) -> T;
+    fn visit_if(&self, stmt: &If) -> T;
+    fn visit_while(&self, stmt: &While) -> T;
+    fn visit_break(&self, stmt: &Break) -> T;
+    fn visit_continue(&self, stmt: &Continue) -> T;
+    fn visit_function(&self, stmt: &Function) -> T;
+    fn visit_return(&self, stmt: &Return) -> T;
+    fn visit_class(&self, stmt: &Class) -> T;
+    fn visit_stmt(&self, stmt: &Stmt) -> T {
+        match stmt {
+            Stmt::ExprStmt(inner) => self.visit_expr_stmt(inner),
+            Stmt::PrintStmt(inner) => self.visit_print_stmt(inner),
+            Stmt::Var(inner) => self.visit_var_stmt(inner),
+            Stmt::Block(inner) => self.visit_block(inner),
+            Stmt::If(inner) => self.visit_if(inner),
+            Stmt::While(inner) => self.visit_while(inner),
+            Stmt::Break(inner) => self.visit_break(inner),
+            Stmt::Continue(inner) => self.visit_continue(inner),
+            Stmt::Function(inner) => self.visit_function(inner),
+            Stmt::Return(inner) => self.visit_return(inner),
+            Stmt::Class(inner) => self.visit_class(inner),
+        }
+    }
+}
+
+pub trait OwnedStmtVisitor<T> {
+    fn visit_expr_stmt(&self, stmt: ExprStmt) -> T;
+    fn visit_print_stmt(&self, stmt: PrintStmt) -> T;
+    fn visit_var_stmt(&self, stmt: Var) -> T;
+    fn visit_block(&self, block: Block) -> T;
+    fn visit_if(&self, stmt: If) -> T;
+    fn visit_while(&self, stmt: While) -> T;
+    fn visit_break(&self, stmt: Break) -> T;
+    fn visit_continue(&self, stmt: Continue) -> T;
+    fn visit_function(&self, stmt: Function) -> T;
+    fn visit_return(&self, stmt: Return) -> T;
+    fn visit_class(&self, stmt: Class) -> T;
+    fn visit_stmt(&self, stmt: Stmt) -> T {
+        match stmt {
+            Stmt::ExprStmt(inner) => self.visit_expr_stmt(inner),
+            Stmt::PrintStmt(inner) => self.visit_print_stmt(inner),
+            Stmt::Var(inner) => self.visit_var_stmt(inner),
+            Stmt::Block(inner) => self.visit_block(inner),
+            Stmt::If(inner) => self.visit_if(inner),
+            Stmt::While(inner) => self.visit_while(inner),
+            Stmt::Break(inner) => self.visit_break(inner),
+            Stmt::Continue(inner) => self.visit_continue(inner),
+            Stmt::Function(inner) => self.visit_function(inner),
+            Stmt::Return(inner) => self.visit_return(inner),
+            Stmt::Class(inner) => self.visit_class(inner),
         }
     }
 }
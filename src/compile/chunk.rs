@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::format_number;
+use crate::lex::Span;
+
+/// A single bytecode instruction, operands follow the opcode byte in the chunk
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+#[allow(non_camel_case_types)]
+pub enum OpCode {
+    /// u16 constant index
+    CONSTANT,
+    NIL,
+    TRUE,
+    FALSE,
+    POP,
+    /// u8 stack slot
+    GET_LOCAL,
+    /// u8 stack slot
+    SET_LOCAL,
+    /// u16 constant index of the name
+    GET_GLOBAL,
+    /// u16 constant index of the name
+    DEFINE_GLOBAL,
+    /// u16 constant index of the name
+    SET_GLOBAL,
+    /// u8 upvalue index
+    GET_UPVALUE,
+    /// u8 upvalue index
+    SET_UPVALUE,
+    /// u16 constant index of the name
+    GET_PROPERTY,
+    /// u16 constant index of the name
+    SET_PROPERTY,
+    /// u16 constant index of the name
+    GET_SUPER,
+    EQUAL,
+    NOT_EQUAL,
+    GREATER,
+    GREATER_EQUAL,
+    LESS,
+    LESS_EQUAL,
+    ADD,
+    SUBTRACT,
+    MULTIPLY,
+    DIVIDE,
+    NOT,
+    NEGATE,
+    PRINT,
+    /// replaces the value on top of the stack with how `print` shows it
+    STRINGIFY,
+    /// u16 forward offset
+    JUMP,
+    /// u16 forward offset, leaves the condition on the stack
+    JUMP_IF_FALSE,
+    /// u16 backward offset
+    LOOP,
+    /// u8 argument count
+    CALL,
+    /// u16 constant index of the function, then a (u8 is_local, u8 index) pair per upvalue
+    CLOSURE,
+    CLOSE_UPVALUE,
+    RETURN,
+    /// u16 constant index of the name
+    CLASS,
+    INHERIT,
+    /// u16 constant index of the name
+    METHOD,
+}
+
+impl OpCode {
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        use OpCode::*;
+
+        const OPCODES: [OpCode; 39] = [
+            CONSTANT,
+            NIL,
+            TRUE,
+            FALSE,
+            POP,
+            GET_LOCAL,
+            SET_LOCAL,
+            GET_GLOBAL,
+            DEFINE_GLOBAL,
+            SET_GLOBAL,
+            GET_UPVALUE,
+            SET_UPVALUE,
+            GET_PROPERTY,
+            SET_PROPERTY,
+            GET_SUPER,
+            EQUAL,
+            NOT_EQUAL,
+            GREATER,
+            GREATER_EQUAL,
+            LESS,
+            LESS_EQUAL,
+            ADD,
+            SUBTRACT,
+            MULTIPLY,
+            DIVIDE,
+            NOT,
+            NEGATE,
+            PRINT,
+            STRINGIFY,
+            JUMP,
+            JUMP_IF_FALSE,
+            LOOP,
+            CALL,
+            CLOSURE,
+            CLOSE_UPVALUE,
+            RETURN,
+            CLASS,
+            INHERIT,
+            METHOD,
+        ];
+        OPCODES.get(byte as usize).copied()
+    }
+}
+
+/// A value known at compile time, stored in a chunk's constant pool
+#[derive(Clone, Debug)]
+pub enum Constant {
+    Number(f64),
+    String(Rc<str>),
+    Function(Rc<CompiledFunction>),
+}
+
+impl PartialEq for Constant {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            // compare bits so that NaN constants are deduplicated too
+            (Constant::Number(l), Constant::Number(r)) => l.to_bits() == r.to_bits(),
+            (Constant::String(l), Constant::String(r)) => l == r,
+            (Constant::Function(l), Constant::Function(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Constant::Number(n) => write!(f, "{}", format_number(*n)),
+            Constant::String(s) => write!(f, "{}", s),
+            Constant::Function(function) => write!(f, "{}", function),
+        }
+    }
+}
+
+/// What a number or string constant is looked up by when deduplicating the pool
+///
+/// Numbers go by their bits so that NaN constants are deduplicated too.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ConstantKey {
+    Number(u64),
+    String(Rc<str>),
+}
+
+/// A function lowered to bytecode, the top level of a program is compiled into one called `<script>`
+#[derive(Debug)]
+pub struct CompiledFunction {
+    pub name: String,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
+
+impl std::fmt::Display for CompiledFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "<script>")
+        } else {
+            write!(f, "<fn {}>", self.name)
+        }
+    }
+}
+
+/// A sequence of bytecode with its constant pool and span table
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Constant>,
+    /// where each number and string is in `constants`, functions are never shared
+    constant_indices: HashMap<ConstantKey, usize>,
+    /// run-length encoded source spans, `(span of the token, number of bytes)`
+    spans: Vec<(Span, usize)>,
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Chunk {
+            code: Vec::new(),
+            constants: Vec::new(),
+            constant_indices: HashMap::new(),
+            spans: Vec::new(),
+        }
+    }
+
+    /// Add a byte, `span` is the token it was compiled from
+    pub fn write(&mut self, byte: u8, span: &Span) {
+        self.code.push(byte);
+        match self.spans.last_mut() {
+            Some((last_span, count)) if last_span == span => *count += 1,
+            _ => self.spans.push((span.clone(), 1)),
+        }
+    }
+
+    /// Add a constant to the pool (reusing an identical one if possible) and return its index
+    pub fn add_constant(&mut self, constant: Constant) -> usize {
+        let key = match &constant {
+            Constant::Number(n) => Some(ConstantKey::Number(n.to_bits())),
+            Constant::String(s) => Some(ConstantKey::String(s.clone())),
+            Constant::Function(_) => None,
+        };
+        if let Some(index) = key.as_ref().and_then(|key| self.constant_indices.get(key)) {
+            return *index;
+        }
+
+        self.constants.push(constant);
+        let index = self.constants.len() - 1;
+        if let Some(key) = key {
+            self.constant_indices.insert(key, index);
+        }
+        index
+    }
+
+    /// The span of the token the instruction at `offset` was compiled from
+    pub fn span_at(&self, offset: usize) -> Span {
+        let mut end = 0;
+        for (span, count) in &self.spans {
+            end += count;
+            if offset < end {
+                return span.clone();
+            }
+        }
+        self.spans
+            .last()
+            .map_or_else(Span::new, |(span, _)| span.clone())
+    }
+
+    /// The source line of the instruction at `offset`
+    pub fn line_at(&self, offset: usize) -> usize {
+        self.span_at(offset).line()
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    /// Human readable listing of the chunk, for debugging
+    pub fn disassemble(&self, name: &str) -> String {
+        let mut output = format!("== {} ==\n", name);
+        let mut offset = 0;
+        while offset < self.code.len() {
+            offset = self.disassemble_instruction(&mut output, offset);
+        }
+        output
+    }
+
+    fn disassemble_instruction(&self, output: &mut String, offset: usize) -> usize {
+        let line = self.line_at(offset);
+        if offset > 0 && line == self.line_at(offset - 1) {
+            output.push_str(&format!("{:04}    | ", offset));
+        } else {
+            output.push_str(&format!("{:04} {:4} ", offset, line));
+        }
+
+        let op = match OpCode::from_byte(self.code[offset]) {
+            Some(op) => op,
+            None => {
+                output.push_str(&format!("unknown opcode {}\n", self.code[offset]));
+                return offset + 1;
+            }
+        };
+
+        match op {
+            OpCode::CONSTANT
+            | OpCode::GET_GLOBAL
+            | OpCode::DEFINE_GLOBAL
+            | OpCode::SET_GLOBAL
+            | OpCode::GET_PROPERTY
+            | OpCode::SET_PROPERTY
+            | OpCode::GET_SUPER
+            | OpCode::CLASS
+            | OpCode::METHOD => {
+                let index = self.read_u16(offset + 1) as usize;
+                output.push_str(&format!("{:?} {} '{}'\n", op, index, self.constants[index]));
+                offset + 3
+            }
+            OpCode::GET_LOCAL
+            | OpCode::SET_LOCAL
+            | OpCode::GET_UPVALUE
+            | OpCode::SET_UPVALUE
+            | OpCode::CALL => {
+                output.push_str(&format!("{:?} {}\n", op, self.code[offset + 1]));
+                offset + 2
+            }
+            OpCode::JUMP | OpCode::JUMP_IF_FALSE => {
+                let jump = self.read_u16(offset + 1) as usize;
+                output.push_str(&format!("{:?} {} -> {}\n", op, offset, offset + 3 + jump));
+                offset + 3
+            }
+            OpCode::LOOP => {
+                let jump = self.read_u16(offset + 1) as usize;
+                output.push_str(&format!("{:?} {} -> {}\n", op, offset, offset + 3 - jump));
+                offset + 3
+            }
+            OpCode::CLOSURE => {
+                let index = self.read_u16(offset + 1) as usize;
+                output.push_str(&format!("{:?} {} {}\n", op, index, self.constants[index]));
+                let upvalue_count = match &self.constants[index] {
+                    Constant::Function(function) => function.upvalue_count,
+                    _ => 0,
+                };
+                let mut offset = offset + 3;
+                for _ in 0..upvalue_count {
+                    let kind = if self.code[offset] == 1 {
+                        "local"
+                    } else {
+                        "upvalue"
+                    };
+                    output.push_str(&format!(
+                        "{:04}    |   {} {}\n",
+                        offset,
+                        kind,
+                        self.code[offset + 1]
+                    ));
+                    offset += 2;
+                }
+                offset
+            }
+            _ => {
+                output.push_str(&format!("{:?}\n", op));
+                offset + 1
+            }
+        }
+    }
+}
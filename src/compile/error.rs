@@ -0,0 +1,69 @@
+use thiserror::Error;
+
+use crate::lex::Token;
+
+/// The Error type returned by `Compiler`
+///
+/// These are limits of the bytecode format, anything else is caught by the parser or resolver.
+#[derive(Clone, Error, Debug)]
+#[error("{kind} Occurred at {token} {message}")]
+pub struct Error {
+    token: Box<Token>,
+    kind: ErrorKind,
+    message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    TooManyConstants,
+    TooManyLocals,
+    TooManyUpvalues,
+    TooManyArguments,
+    JumpTooLarge,
+}
+
+impl Error {
+    pub fn tokened(msg: impl Into<String>, token: Token, kind: ErrorKind) -> Self {
+        Error {
+            kind,
+            message: msg.into(),
+            token: Box::new(token),
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl ErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::TooManyConstants => "C001",
+            ErrorKind::TooManyLocals => "C002",
+            ErrorKind::TooManyUpvalues => "C003",
+            ErrorKind::TooManyArguments => "C004",
+            ErrorKind::JumpTooLarge => "C005",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ErrorKind::TooManyConstants => write!(f, "Too many constants in one chunk."),
+            ErrorKind::TooManyLocals => write!(f, "Too many local variables in function."),
+            ErrorKind::TooManyUpvalues => write!(f, "Too many closure variables in function."),
+            ErrorKind::TooManyArguments => write!(f, "Can't have more than 255 arguments."),
+            ErrorKind::JumpTooLarge => write!(f, "Too much code to jump over."),
+        }
+    }
+}
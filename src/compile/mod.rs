@@ -0,0 +1,796 @@
+// Module declarations
+
+mod chunk;
+pub use chunk::{Chunk, CompiledFunction, Constant, OpCode};
+
+mod error;
+pub use error::{Error, ErrorKind};
+
+// compiler.rs
+
+use std::cell::{RefCell, RefMut};
+use std::rc::Rc;
+
+use crate::ast::{visitor::StmtVisitor, *};
+use crate::lex::{Span, Token, TokenKind};
+use crate::Visitor;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum FunctionType {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+/// A local variable living in a stack slot of the function being compiled
+struct Local {
+    name: String,
+    depth: usize,
+    /// captured locals are moved to the heap when they go out of scope instead of being popped
+    is_captured: bool,
+}
+
+/// Where a closure finds a captured variable when it is created
+#[derive(Copy, Clone, PartialEq)]
+struct Upvalue {
+    index: u8,
+    /// a local of the enclosing function, otherwise one of the enclosing function's own upvalues
+    is_local: bool,
+}
+
+/// Jumps out of the innermost loop that still need to be patched
+struct Loop {
+    scope_depth: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+/// Everything needed to compile one function, nested functions push a new one of these
+struct FunctionState {
+    function_type: FunctionType,
+    name: String,
+    arity: usize,
+    chunk: Chunk,
+    locals: Vec<Local>,
+    upvalues: Vec<Upvalue>,
+    scope_depth: usize,
+    loops: Vec<Loop>,
+}
+
+impl FunctionState {
+    fn new(function_type: FunctionType, name: impl Into<String>) -> Self {
+        // slot zero holds the function being called, or the receiver inside methods
+        let slot_zero = match function_type {
+            FunctionType::Method | FunctionType::Initializer => "this",
+            _ => "",
+        };
+        FunctionState {
+            function_type,
+            name: name.into(),
+            arity: 0,
+            chunk: Chunk::new(),
+            locals: vec![Local {
+                name: slot_zero.to_string(),
+                depth: 0,
+                is_captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            loops: Vec::new(),
+        }
+    }
+
+    fn resolve_local(&self, name: &str) -> Option<usize> {
+        self.locals.iter().rposition(|local| local.name == name)
+    }
+}
+
+/// Lowers a resolved program to bytecode for the `vm`
+///
+/// Resolution errors are expected to have been reported already, the compiler only fails
+/// when a program runs into the limits of the bytecode format.
+pub struct Compiler {
+    /// The function being compiled is last, the enclosing functions come before it
+    functions: RefCell<Vec<FunctionState>>,
+    /// The last token seen, used for the span table and error locations
+    token: RefCell<Token>,
+    /// Print the value of top level expression statements, for the REPL
+    echo_expressions: bool,
+}
+
+impl Compiler {
+    fn new(echo_expressions: bool) -> Self {
+        Compiler {
+            functions: RefCell::new(vec![FunctionState::new(FunctionType::Script, "")]),
+            token: RefCell::new(Token {
+                kind: TokenKind::EOF,
+                lexeme: String::new(),
+                literal: None,
+                span: Span::new(),
+                trivia: Vec::new(),
+            }),
+            echo_expressions,
+        }
+    }
+
+    /// Compile a whole program into the function for its top level code
+    pub fn compile(statements: &[Stmt]) -> Result<Rc<CompiledFunction>, Error> {
+        Compiler::new(false).compile_script(statements)
+    }
+
+    /// Like `compile`, but top level expression statements print their value
+    pub fn compile_repl(statements: &[Stmt]) -> Result<Rc<CompiledFunction>, Error> {
+        Compiler::new(true).compile_script(statements)
+    }
+
+    fn compile_script(self, statements: &[Stmt]) -> Result<Rc<CompiledFunction>, Error> {
+        for statement in statements {
+            match statement {
+                Stmt::ExprStmt(ExprStmt(expr)) if self.echo_expressions => {
+                    self.visit_expr(expr)?;
+                    self.emit_op(OpCode::PRINT);
+                }
+                statement => self.visit_stmt(statement)?,
+            }
+        }
+        self.emit_return();
+
+        let state = self.functions.borrow_mut().pop().unwrap();
+        Ok(Rc::new(CompiledFunction {
+            name: state.name,
+            arity: state.arity,
+            upvalue_count: state.upvalues.len(),
+            chunk: state.chunk,
+        }))
+    }
+
+    /// The function currently being compiled
+    fn current(&self) -> RefMut<'_, FunctionState> {
+        RefMut::map(self.functions.borrow_mut(), |functions| {
+            functions.last_mut().unwrap()
+        })
+    }
+
+    fn set_token(&self, token: &Token) {
+        *self.token.borrow_mut() = token.clone();
+    }
+
+    fn error(&self, kind: ErrorKind) -> Error {
+        Error::tokened("", self.token.borrow().clone(), kind)
+    }
+
+    //
+    // Emitting bytecode
+    //
+
+    fn emit_byte(&self, byte: u8) {
+        let token = self.token.borrow();
+        self.current().chunk.write(byte, &token.span);
+    }
+
+    fn emit_op(&self, op: OpCode) {
+        self.emit_byte(op as u8);
+    }
+
+    fn emit_op_u8(&self, op: OpCode, operand: u8) {
+        self.emit_op(op);
+        self.emit_byte(operand);
+    }
+
+    fn emit_op_u16(&self, op: OpCode, operand: u16) {
+        self.emit_op(op);
+        for byte in operand.to_be_bytes().iter() {
+            self.emit_byte(*byte);
+        }
+    }
+
+    fn make_constant(&self, constant: Constant) -> Result<u16, Error> {
+        let index = self.current().chunk.add_constant(constant);
+        if index > u16::MAX as usize {
+            return Err(self.error(ErrorKind::TooManyConstants));
+        }
+        Ok(index as u16)
+    }
+
+    fn identifier_constant(&self, name: &str) -> Result<u16, Error> {
+        self.make_constant(Constant::String(name.into()))
+    }
+
+    /// Emit a jump with a placeholder offset, returning where to patch it
+    fn emit_jump(&self, op: OpCode) -> usize {
+        self.emit_op_u16(op, u16::MAX);
+        self.current().chunk.code.len() - 2
+    }
+
+    /// Point a jump emitted by `emit_jump` at the next instruction
+    fn patch_jump(&self, offset: usize) -> Result<(), Error> {
+        let mut current = self.current();
+        let jump = current.chunk.code.len() - offset - 2;
+        if jump > u16::MAX as usize {
+            drop(current);
+            return Err(self.error(ErrorKind::JumpTooLarge));
+        }
+        let bytes = (jump as u16).to_be_bytes();
+        current.chunk.code[offset] = bytes[0];
+        current.chunk.code[offset + 1] = bytes[1];
+        Ok(())
+    }
+
+    fn emit_loop(&self, start: usize) -> Result<(), Error> {
+        // the offset also has to skip over the LOOP instruction itself
+        let offset = self.current().chunk.code.len() + 3 - start;
+        if offset > u16::MAX as usize {
+            return Err(self.error(ErrorKind::JumpTooLarge));
+        }
+        self.emit_op_u16(OpCode::LOOP, offset as u16);
+        Ok(())
+    }
+
+    /// The implicit return at the end of every function
+    fn emit_return(&self) {
+        if self.current().function_type == FunctionType::Initializer {
+            self.emit_op_u8(OpCode::GET_LOCAL, 0);
+        } else {
+            self.emit_op(OpCode::NIL);
+        }
+        self.emit_op(OpCode::RETURN);
+    }
+
+    //
+    // Scopes and variables
+    //
+
+    fn begin_scope(&self) {
+        self.current().scope_depth += 1;
+    }
+
+    fn end_scope(&self) {
+        let captured = {
+            let mut current = self.current();
+            current.scope_depth -= 1;
+            let depth = current.scope_depth;
+            let mut captured = Vec::new();
+            while let Some(local) = current.locals.last() {
+                if local.depth <= depth {
+                    break;
+                }
+                captured.push(local.is_captured);
+                current.locals.pop();
+            }
+            captured
+        };
+        for is_captured in captured {
+            self.emit_pop_local(is_captured);
+        }
+    }
+
+    fn emit_pop_local(&self, is_captured: bool) {
+        if is_captured {
+            self.emit_op(OpCode::CLOSE_UPVALUE);
+        } else {
+            self.emit_op(OpCode::POP);
+        }
+    }
+
+    fn is_global_scope(&self) -> bool {
+        self.current().scope_depth == 0
+    }
+
+    /// Declare a local in the innermost scope, its value is whatever ends up in the next stack slot
+    fn add_local(&self, name: &Token) -> Result<(), Error> {
+        let mut current = self.current();
+        if current.locals.len() > u8::MAX as usize {
+            drop(current);
+            self.set_token(name);
+            return Err(self.error(ErrorKind::TooManyLocals));
+        }
+        let depth = current.scope_depth;
+        current.locals.push(Local {
+            name: name.name().to_string(),
+            depth,
+            is_captured: false,
+        });
+        Ok(())
+    }
+
+    /// Bind the value on top of the stack to a newly declared variable
+    fn define_variable(&self, name: &Token) -> Result<(), Error> {
+        if self.is_global_scope() {
+            let index = self.identifier_constant(name.name())?;
+            self.emit_op_u16(OpCode::DEFINE_GLOBAL, index);
+            Ok(())
+        } else {
+            self.add_local(name)
+        }
+    }
+
+    fn resolve_upvalue(&self, function: usize, name: &str) -> Result<Option<u8>, Error> {
+        if function == 0 {
+            return Ok(None);
+        }
+
+        let local = self.functions.borrow()[function - 1].resolve_local(name);
+        if let Some(local) = local {
+            self.functions.borrow_mut()[function - 1].locals[local].is_captured = true;
+            return self.add_upvalue(function, local as u8, true).map(Some);
+        }
+
+        match self.resolve_upvalue(function - 1, name)? {
+            Some(upvalue) => self.add_upvalue(function, upvalue, false).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn add_upvalue(&self, function: usize, index: u8, is_local: bool) -> Result<u8, Error> {
+        let mut functions = self.functions.borrow_mut();
+        let upvalues = &mut functions[function].upvalues;
+        let upvalue = Upvalue { index, is_local };
+
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return Ok(existing as u8);
+        }
+        if upvalues.len() > u8::MAX as usize {
+            drop(functions);
+            return Err(self.error(ErrorKind::TooManyUpvalues));
+        }
+        upvalues.push(upvalue);
+        Ok((upvalues.len() - 1) as u8)
+    }
+
+    /// Emit a read of a variable, or a write of the value on top of the stack when `assign` is set
+    fn named_variable(&self, name: &Token, assign: bool) -> Result<(), Error> {
+        self.set_token(name);
+        let name = name.name();
+
+        let local = self.current().resolve_local(name);
+        if let Some(slot) = local {
+            let op = if assign {
+                OpCode::SET_LOCAL
+            } else {
+                OpCode::GET_LOCAL
+            };
+            self.emit_op_u8(op, slot as u8);
+            return Ok(());
+        }
+
+        let function = self.functions.borrow().len() - 1;
+        if let Some(index) = self.resolve_upvalue(function, name)? {
+            let op = if assign {
+                OpCode::SET_UPVALUE
+            } else {
+                OpCode::GET_UPVALUE
+            };
+            self.emit_op_u8(op, index);
+            return Ok(());
+        }
+
+        let index = self.identifier_constant(name)?;
+        let op = if assign {
+            OpCode::SET_GLOBAL
+        } else {
+            OpCode::GET_GLOBAL
+        };
+        self.emit_op_u16(op, index);
+        Ok(())
+    }
+
+    /// Compile a function body into its own chunk and emit a closure for it
+    fn function(&self, function: &Function, function_type: FunctionType) -> Result<(), Error> {
+        self.set_token(&function.name);
+        self.functions
+            .borrow_mut()
+            .push(FunctionState::new(function_type, function.name.name()));
+
+        self.begin_scope();
+        self.current().arity = function.params.len();
+        for param in &function.params {
+            self.add_local(param)?;
+        }
+        for statement in &function.body {
+            self.visit_stmt(statement)?;
+        }
+        self.emit_return();
+
+        let state = self.functions.borrow_mut().pop().unwrap();
+        let upvalues = state.upvalues;
+        let compiled = CompiledFunction {
+            name: state.name,
+            arity: state.arity,
+            upvalue_count: upvalues.len(),
+            chunk: state.chunk,
+        };
+
+        let index = self.make_constant(Constant::Function(Rc::new(compiled)))?;
+        self.emit_op_u16(OpCode::CLOSURE, index);
+        for upvalue in upvalues {
+            self.emit_byte(upvalue.is_local as u8);
+            self.emit_byte(upvalue.index);
+        }
+        Ok(())
+    }
+
+    /// Emit the pops needed to leave the scopes inside the innermost loop, without ending them
+    fn exit_loop_scopes(&self) {
+        let captured = {
+            let current = self.current();
+            let depth = current.loops.last().map_or(0, |l| l.scope_depth);
+            current
+                .locals
+                .iter()
+                .rev()
+                .take_while(|local| local.depth > depth)
+                .map(|local| local.is_captured)
+                .collect::<Vec<_>>()
+        };
+        for is_captured in captured {
+            self.emit_pop_local(is_captured);
+        }
+    }
+}
+
+impl StmtVisitor<Result<(), Error>> for Compiler {
+    fn visit_expr_stmt(&self, stmt: &ExprStmt) -> Result<(), Error> {
+        self.visit_expr(&stmt.0)?;
+        self.emit_op(OpCode::POP);
+        Ok(())
+    }
+    fn visit_print_stmt(&self, stmt: &PrintStmt) -> Result<(), Error> {
+        self.visit_expr(&stmt.0)?;
+        self.emit_op(OpCode::PRINT);
+        Ok(())
+    }
+    fn visit_var_stmt(&self, stmt: &Var) -> Result<(), Error> {
+        match &stmt.initializer {
+            Some(initializer) => self.visit_expr(initializer)?,
+            None => self.emit_op(OpCode::NIL),
+        }
+        self.set_token(&stmt.name);
+        self.define_variable(&stmt.name)
+    }
+    fn visit_block(&self, block: &Block) -> Result<(), Error> {
+        self.begin_scope();
+        for statement in &block.statements {
+            self.visit_stmt(statement)?;
+        }
+        self.end_scope();
+        Ok(())
+    }
+    fn visit_if(&self, stmt: &If) -> Result<(), Error> {
+        self.visit_expr(&stmt.condition)?;
+        let then_jump = self.emit_jump(OpCode::JUMP_IF_FALSE);
+        self.emit_op(OpCode::POP);
+        self.visit_stmt(&stmt.then_branch)?;
+
+        let else_jump = self.emit_jump(OpCode::JUMP);
+        self.patch_jump(then_jump)?;
+        self.emit_op(OpCode::POP);
+        if let Some(else_branch) = &stmt.else_branch {
+            self.visit_stmt(else_branch)?;
+        }
+        self.patch_jump(else_jump)
+    }
+    fn visit_while(&self, stmt: &While) -> Result<(), Error> {
+        let loop_start = self.current().chunk.code.len();
+        self.visit_expr(&stmt.condition)?;
+        let exit_jump = self.emit_jump(OpCode::JUMP_IF_FALSE);
+        self.emit_op(OpCode::POP);
+
+        let scope_depth = self.current().scope_depth;
+        self.current().loops.push(Loop {
+            scope_depth,
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        });
+        let body = self.visit_stmt(&stmt.body);
+        let Loop {
+            breaks, continues, ..
+        } = self.current().loops.pop().unwrap();
+        body?;
+
+        // `continue` skips the rest of the body but still runs the increment
+        for jump in continues {
+            self.patch_jump(jump)?;
+        }
+        if let Some(increment) = &stmt.increment {
+            self.visit_expr(increment)?;
+            self.emit_op(OpCode::POP);
+        }
+        self.emit_loop(loop_start)?;
+
+        self.patch_jump(exit_jump)?;
+        self.emit_op(OpCode::POP);
+        // `break` jumps past the condition's pop, it was already popped inside the loop
+        for jump in breaks {
+            self.patch_jump(jump)?;
+        }
+        Ok(())
+    }
+    fn visit_break(&self, stmt: &Break) -> Result<(), Error> {
+        self.set_token(&stmt.keyword);
+        self.exit_loop_scopes();
+        let jump = self.emit_jump(OpCode::JUMP);
+        // the parser rejects `break` outside of loops
+        if let Some(innermost) = self.current().loops.last_mut() {
+            innermost.breaks.push(jump);
+        }
+        Ok(())
+    }
+    fn visit_continue(&self, stmt: &Continue) -> Result<(), Error> {
+        self.set_token(&stmt.keyword);
+        self.exit_loop_scopes();
+        let jump = self.emit_jump(OpCode::JUMP);
+        if let Some(innermost) = self.current().loops.last_mut() {
+            innermost.continues.push(jump);
+        }
+        Ok(())
+    }
+    fn visit_function(&self, stmt: &Function) -> Result<(), Error> {
+        if self.is_global_scope() {
+            self.function(stmt, FunctionType::Function)?;
+            self.set_token(&stmt.name);
+            self.define_variable(&stmt.name)
+        } else {
+            // declare the local first so that the function can refer to itself
+            self.add_local(&stmt.name)?;
+            self.function(stmt, FunctionType::Function)
+        }
+    }
+    fn visit_return(&self, stmt: &Return) -> Result<(), Error> {
+        self.set_token(&stmt.keyword);
+        match &stmt.value {
+            Some(value) => {
+                self.visit_expr(value)?;
+                self.emit_op(OpCode::RETURN);
+            }
+            None => self.emit_return(),
+        }
+        Ok(())
+    }
+    fn visit_class(&self, stmt: &Class) -> Result<(), Error> {
+        self.set_token(&stmt.name);
+        let name = self.identifier_constant(stmt.name.name())?;
+        if !self.is_global_scope() {
+            self.add_local(&stmt.name)?;
+        }
+        self.emit_op_u16(OpCode::CLASS, name);
+        if self.is_global_scope() {
+            self.define_variable(&stmt.name)?;
+        }
+
+        // methods capture the superclass as a local called `super` in a scope around the class body
+        if let Some(superclass) = &stmt.superclass {
+            self.named_variable(&superclass.name, false)?;
+            self.begin_scope();
+            self.add_local(&Token {
+                lexeme: "super".to_string(),
+                literal: None,
+                ..superclass.name.clone()
+            })?;
+            self.named_variable(&stmt.name, false)?;
+            self.emit_op(OpCode::INHERIT);
+        }
+
+        self.named_variable(&stmt.name, false)?;
+        for method in &stmt.methods {
+            let function_type = if method.name.name() == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.function(method, function_type)?;
+            let name = self.identifier_constant(method.name.name())?;
+            self.emit_op_u16(OpCode::METHOD, name);
+        }
+        self.emit_op(OpCode::POP);
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+        Ok(())
+    }
+}
+
+impl Visitor<Result<(), Error>> for Compiler {
+    fn visit_grouping(&self, grouping: &Grouping) -> Result<(), Error> {
+        self.visit_expr(&grouping.0)
+    }
+    fn visit_binary(&self, binary: &Binary) -> Result<(), Error> {
+        self.visit_expr(&binary.left)?;
+        self.visit_expr(&binary.right)?;
+        self.set_token(&binary.operator);
+        let op = match binary.operator.kind {
+            TokenKind::EQUAL_EQUAL => OpCode::EQUAL,
+            TokenKind::BANG_EQUAL => OpCode::NOT_EQUAL,
+            TokenKind::GREATER => OpCode::GREATER,
+            TokenKind::GREATER_EQUAL => OpCode::GREATER_EQUAL,
+            TokenKind::LESS => OpCode::LESS,
+            TokenKind::LESS_EQUAL => OpCode::LESS_EQUAL,
+            TokenKind::PLUS => OpCode::ADD,
+            TokenKind::MINUS => OpCode::SUBTRACT,
+            TokenKind::STAR => OpCode::MULTIPLY,
+            TokenKind::SLASH => OpCode::DIVIDE,
+            _ => unreachable!(
+                "cannot compile this token in a binary expression, bad input to compiler"
+            ),
+        };
+        self.emit_op(op);
+        Ok(())
+    }
+    fn visit_unary(&self, unary: &Unary) -> Result<(), Error> {
+        self.visit_expr(&unary.right)?;
+        self.set_token(&unary.operator);
+        let op = match unary.operator.kind {
+            TokenKind::MINUS => OpCode::NEGATE,
+            TokenKind::BANG => OpCode::NOT,
+            _ => unreachable!(
+                "cannot compile this token in a unary expression, bad input to compiler"
+            ),
+        };
+        self.emit_op(op);
+        Ok(())
+    }
+    fn visit_literal(&self, literal_value: &LiteralValue) -> Result<(), Error> {
+        match literal_value {
+            LiteralValue::Number(n) => {
+                let index = self.make_constant(Constant::Number(*n))?;
+                self.emit_op_u16(OpCode::CONSTANT, index);
+            }
+            LiteralValue::String(s) => {
+                let index = self.make_constant(Constant::String(s.as_str().into()))?;
+                self.emit_op_u16(OpCode::CONSTANT, index);
+            }
+            LiteralValue::Bool(true) => self.emit_op(OpCode::TRUE),
+            LiteralValue::Bool(false) => self.emit_op(OpCode::FALSE),
+            LiteralValue::Nil => self.emit_op(OpCode::NIL),
+        }
+        Ok(())
+    }
+    fn visit_variable(&self, variable: &Variable) -> Result<(), Error> {
+        self.named_variable(&variable.name, false)
+    }
+    fn visit_assign(&self, assign: &Assign) -> Result<(), Error> {
+        self.visit_expr(&assign.value)?;
+        self.named_variable(&assign.name, true)
+    }
+    fn visit_logical(&self, logical: &Logical) -> Result<(), Error> {
+        self.visit_expr(&logical.left)?;
+        self.set_token(&logical.operator);
+        match logical.operator.kind {
+            TokenKind::OR => {
+                let else_jump = self.emit_jump(OpCode::JUMP_IF_FALSE);
+                let end_jump = self.emit_jump(OpCode::JUMP);
+                self.patch_jump(else_jump)?;
+                self.emit_op(OpCode::POP);
+                self.visit_expr(&logical.right)?;
+                self.patch_jump(end_jump)
+            }
+            TokenKind::AND => {
+                let end_jump = self.emit_jump(OpCode::JUMP_IF_FALSE);
+                self.emit_op(OpCode::POP);
+                self.visit_expr(&logical.right)?;
+                self.patch_jump(end_jump)
+            }
+            _ => unreachable!(
+                "cannot compile this token in a logical expression, bad input to compiler"
+            ),
+        }
+    }
+    fn visit_call(&self, call: &Call) -> Result<(), Error> {
+        self.visit_expr(&call.callee)?;
+        for argument in &call.arguments {
+            self.visit_expr(argument)?;
+        }
+        self.set_token(&call.paren);
+        if call.arguments.len() > u8::MAX as usize {
+            return Err(self.error(ErrorKind::TooManyArguments));
+        }
+        self.emit_op_u8(OpCode::CALL, call.arguments.len() as u8);
+        Ok(())
+    }
+    fn visit_get(&self, get: &Get) -> Result<(), Error> {
+        self.visit_expr(&get.object)?;
+        self.set_token(&get.name);
+        let name = self.identifier_constant(get.name.name())?;
+        self.emit_op_u16(OpCode::GET_PROPERTY, name);
+        Ok(())
+    }
+    fn visit_set(&self, set: &Set) -> Result<(), Error> {
+        self.visit_expr(&set.object)?;
+        self.visit_expr(&set.value)?;
+        self.set_token(&set.name);
+        let name = self.identifier_constant(set.name.name())?;
+        self.emit_op_u16(OpCode::SET_PROPERTY, name);
+        Ok(())
+    }
+    fn visit_this(&self, this: &This) -> Result<(), Error> {
+        self.named_variable(&this.keyword, false)
+    }
+    fn visit_super(&self, super_method: &Super) -> Result<(), Error> {
+        let this = Token {
+            kind: TokenKind::THIS,
+            lexeme: "this".to_string(),
+            literal: None,
+            span: super_method.keyword.span.clone(),
+            trivia: Vec::new(),
+        };
+        self.named_variable(&this, false)?;
+        self.named_variable(&super_method.keyword, false)?;
+        self.set_token(&super_method.method);
+        let name = self.identifier_constant(super_method.method.name())?;
+        self.emit_op_u16(OpCode::GET_SUPER, name);
+        Ok(())
+    }
+    fn visit_interpolation(&self, interpolation: &Interpolation) -> Result<(), Error> {
+        // every part is made into a string and then they're all added together
+        for (i, part) in interpolation.parts.iter().enumerate() {
+            self.visit_expr(part)?;
+            if !matches!(part, Expr::Literal(LiteralValue::String(_))) {
+                self.emit_op(OpCode::STRINGIFY);
+            }
+            if i > 0 {
+                self.emit_op(OpCode::ADD);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Compiler, OpCode};
+    use crate::{ast::Stmt, Lexer};
+
+    fn compile(source: &str) -> super::CompiledFunction {
+        let statements = Lexer::new(source)
+            .advance_to_parsing()
+            .collect::<Result<Vec<Stmt>, _>>()
+            .unwrap();
+        std::rc::Rc::try_unwrap(Compiler::compile(&statements).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_compile_expression() {
+        let script = compile("print 1 + 2 * 3;");
+        let ops = [
+            OpCode::CONSTANT,
+            OpCode::CONSTANT,
+            OpCode::CONSTANT,
+            OpCode::MULTIPLY,
+            OpCode::ADD,
+            OpCode::PRINT,
+            OpCode::NIL,
+            OpCode::RETURN,
+        ];
+        let mut expected = Vec::new();
+        for (i, op) in ops.iter().enumerate() {
+            expected.push(*op as u8);
+            if *op == OpCode::CONSTANT {
+                expected.extend_from_slice(&[0, i as u8]);
+            }
+        }
+        assert_eq!(expected, script.chunk.code);
+        assert_eq!(3, script.chunk.constants.len());
+    }
+
+    #[test]
+    fn test_locals_use_stack_slots() {
+        let script = compile("{ var a = 1; var b = a; }");
+        let listing = script.chunk.disassemble("test");
+        assert!(listing.contains("GET_LOCAL 1"));
+        assert!(!listing.contains("GLOBAL"));
+    }
+
+    #[test]
+    fn test_constants_are_shared() {
+        let script = compile("var a = 1; print a + 1; print \"a\" + \"a\"; fun f() {} fun g() {}");
+        let constants = script
+            .chunk
+            .constants
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        // two empty functions are still different functions
+        assert_eq!(vec!["1", "a", "<fn f>", "f", "<fn g>", "g"], constants);
+    }
+}
@@ -0,0 +1,733 @@
+//! Reporting errors, for humans or for tools
+//!
+//! Every error is turned into a `Diagnostic`, which a `DiagnosticSink` then reports.
+//! `HumanSink` renders them rustc style,
+//!
+//! ```text
+//! error[E003]: Bad Addition
+//!  --> script.lox:6:9
+//!   |
+//! 6 | print a + nil;
+//!   |         ^
+//! ```
+//!
+//! and `JsonSink` writes one JSON object per line, see `Record::to_json`.
+//!
+//! # Error codes
+//!
+//! Every `ErrorKind` has a stable code from `ErrorKind::code`, codes are never reused or renumbered.
+//! The runtime codes are shared by the tree-walking evaluator and the bytecode VM.
+//!
+//! | Code | Error |
+//! |------|-------|
+//! | L001 | Unterminated string |
+//! | L002 | Invalid escape character |
+//! | L003 | Unexpected character |
+//! | L004 | Unexpected end of source code |
+//! | L005 | Unterminated block comment |
+//! | L006 | Malformed number literal |
+//! | L007 | Unclosed `${` in a string |
+//! | L900 | Internal interpreter error (lexer) |
+//! | L901 | Fatal lexer error |
+//! | L999 | Unknown lexer error |
+//! | P001 | End of source code while recovering from an error |
+//! | P002 | Invalid expression |
+//! | P003 | Expected `(` |
+//! | P004 | Unclosed `(` |
+//! | P005 | Expected `{` |
+//! | P006 | Unclosed `{` |
+//! | P007 | Unexpected end of source code |
+//! | P008 | Expression statement missing `;` |
+//! | P009 | Statement missing `;` |
+//! | P010 | Expected an identifier |
+//! | P011 | Expected `.` |
+//! | P012 | Invalid assignment target |
+//! | P013 | `break` outside of a loop |
+//! | P014 | `continue` outside of a loop |
+//! | P015 | Too many arguments |
+//! | P016 | Interpolated expression not followed by the rest of the string |
+//! | P900 | Internal interpreter error (parser) |
+//! | P901 | Fatal parser error |
+//! | P999 | Unknown parser error |
+//! | R001 | Local variable read in its own initializer |
+//! | R002 | Variable already declared in this scope |
+//! | R003 | `return` at the top level |
+//! | R004 | `return` with a value in an initializer |
+//! | R005 | `this` outside of a class |
+//! | R006 | `super` outside of a class |
+//! | R007 | `super` in a class without a superclass |
+//! | R008 | Class inherits from itself |
+//! | C001 | Too many constants in one chunk |
+//! | C002 | Too many local variables in a function |
+//! | C003 | Too many closure variables in a function |
+//! | C004 | Too many arguments |
+//! | C005 | Jump too large |
+//! | E001 | Negating something that isn't a number |
+//! | E002 | Bad boolean negation |
+//! | E003 | Bad operands for `+` |
+//! | E004 | Bad operands for `-` |
+//! | E005 | Bad operands for `*` |
+//! | E006 | Bad operands for `/` |
+//! | E007 | Repeating a string a non-integer number of times |
+//! | E008 | Comparing things that aren't numbers |
+//! | E009 | Undefined variable |
+//! | E010 | Calling something that isn't callable |
+//! | E011 | Wrong number of arguments |
+//! | E012 | Undefined property |
+//! | E013 | Property access on something that isn't an instance |
+//! | E014 | Superclass isn't a class |
+//! | E015 | Error raised by a native function |
+//! | E016 | Stack overflow |
+//! | E900 | Invalid bytecode |
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+use crate::{
+    error::RuntimeError,
+    evaluate, lex,
+    lex::{SourceMap, Span},
+    parse, resolve, vm, LoxError,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Where in the source a diagnostic points
+#[derive(Clone, Debug, PartialEq)]
+pub enum Location {
+    Span(Span),
+    Unknown,
+}
+
+/// A single message to show the user, independent of how it ends up being displayed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The stable code of the error kind, see the module documentation
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub location: Location,
+    /// Shown next to the caret
+    pub label: String,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, location: Location) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code: None,
+            message: message.into(),
+            location,
+            label: String::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = label.into();
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    /// One diagnostic per error, a `LoxError` can hold several
+    pub fn from_error(error: &LoxError) -> Vec<Diagnostic> {
+        match error {
+            LoxError::LexerError(error) => vec![Diagnostic::from_lex(error)],
+            LoxError::LexerErrors(errors) => errors.iter().map(Diagnostic::from_lex).collect(),
+            LoxError::ParserError(error) => vec![Diagnostic::from_parse(error)],
+            LoxError::ParserErrors(errors) => errors.iter().map(Diagnostic::from_parse).collect(),
+            LoxError::ResolverErrors(errors) => {
+                errors.iter().map(Diagnostic::from_resolve).collect()
+            }
+            LoxError::CompileError(error) => vec![Diagnostic::error(
+                error.kind().to_string(),
+                Location::Span(error.token().span.clone()),
+            )
+            .with_code(error.kind().code())
+            .with_label(error.message())],
+            LoxError::RuntimeError(RuntimeError::EvaluationError(error)) => {
+                vec![Diagnostic::from_evaluate(error)]
+            }
+            LoxError::RuntimeError(RuntimeError::VmError(error)) => {
+                vec![Diagnostic::from_vm(error)]
+            }
+            LoxError::IoError(error) => vec![Diagnostic::error(
+                format!("Couldn't read the script: {}", error),
+                Location::Unknown,
+            )],
+        }
+    }
+
+    fn from_lex(error: &lex::Error) -> Self {
+        let diagnostic = Diagnostic::error(
+            error.kind().to_string(),
+            Location::Span(error.span().clone()),
+        )
+        .with_code(error.kind().code())
+        .with_label(error.message());
+        match error.kind() {
+            lex::ErrorKind::UnterminatedString(_) => {
+                diagnostic.with_help("add a closing `\"` to end the string")
+            }
+            lex::ErrorKind::UnterminatedComment => diagnostic
+                .with_help("add a closing `*/`, block comments nest so every `/*` needs one"),
+            lex::ErrorKind::UnterminatedInterpolation => diagnostic
+                .with_help("add a `}` after the expression, or write `\\${` for a literal `${`"),
+            lex::ErrorKind::InvalidNumber => diagnostic.with_help(
+                "numbers look like `42`, `3.14`, `1_000`, `6.02e23`, `0x1F` or `0b1010`",
+            ),
+            lex::ErrorKind::InvalidEscape(_) => diagnostic.with_help(
+                r#"the valid escapes are `\n`, `\t`, `\r`, `\\`, `\"`, `\$`, `\x41` and `\u{1F600}`, or write a raw string like `r"C:\path"`"#,
+            ),
+            _ => diagnostic,
+        }
+    }
+
+    fn from_parse(error: &parse::Error) -> Self {
+        let location = match error.token() {
+            Some(token) => Location::Span(token.span.clone()),
+            None => Location::Unknown,
+        };
+        let diagnostic = Diagnostic::error(error.kind().to_string(), location)
+            .with_code(error.kind().code())
+            .with_label(error.message());
+        match error.kind() {
+            parse::ErrorKind::MissingSemicolon | parse::ErrorKind::ExprStmtMissingSemicolon => {
+                diagnostic.with_help("add a `;` at the end of the statement")
+            }
+            parse::ErrorKind::BreakOutsideLoop | parse::ErrorKind::ContinueOutsideLoop => {
+                diagnostic.with_note("`break` and `continue` can only be used inside a loop")
+            }
+            parse::ErrorKind::InvalidAssignmentTarget => {
+                diagnostic.with_note("only variables and fields can be assigned to")
+            }
+            _ => diagnostic,
+        }
+    }
+
+    fn from_resolve(error: &resolve::Error) -> Self {
+        let diagnostic = Diagnostic::error(
+            error.kind().to_string(),
+            Location::Span(error.token().span.clone()),
+        )
+        .with_code(error.kind().code())
+        .with_label(error.message());
+        match error.kind() {
+            resolve::ErrorKind::ReadLocalInOwnInitializer => {
+                diagnostic.with_help("give the new variable a different name")
+            }
+            _ => diagnostic,
+        }
+    }
+
+    fn from_evaluate(error: &evaluate::Error) -> Self {
+        let diagnostic = Diagnostic::error(
+            error.kind().to_string(),
+            Location::Span(error.token().span.clone()),
+        )
+        .with_code(error.kind().code())
+        .with_label(error.message());
+        match error.kind() {
+            evaluate::ErrorKind::UndefinedVariable(_) => {
+                diagnostic.with_help("declare it with `var` before using it")
+            }
+            _ => diagnostic,
+        }
+    }
+
+    fn from_vm(error: &vm::Error) -> Self {
+        let diagnostic = Diagnostic::error(
+            error.kind().to_string(),
+            Location::Span(error.span().clone()),
+        )
+        .with_code(error.kind().code())
+        .with_label(error.message());
+        match error.kind() {
+            vm::ErrorKind::UndefinedVariable(_) => {
+                diagnostic.with_help("declare it with `var` before using it")
+            }
+            _ => diagnostic,
+        }
+    }
+}
+
+/// Turns diagnostics into text, pointing into the source they came from
+pub struct Renderer<'a> {
+    source_map: &'a SourceMap,
+    colour: bool,
+}
+
+// ANSI escape codes
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+impl<'a> Renderer<'a> {
+    pub fn new(source_map: &'a SourceMap) -> Self {
+        Renderer {
+            source_map,
+            colour: false,
+        }
+    }
+
+    /// Highlight the output with ANSI colours, for terminals
+    pub fn with_colour(mut self, colour: bool) -> Self {
+        self.colour = colour;
+        self
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.colour {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Render every diagnostic for an error, separated by blank lines
+    pub fn render_error(&self, error: &LoxError) -> String {
+        Diagnostic::from_error(error)
+            .iter()
+            .map(|diagnostic| self.render(diagnostic))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let severity_style = match diagnostic.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+        let severity = match diagnostic.code {
+            Some(code) => format!("{}[{}]", diagnostic.severity, code),
+            None => diagnostic.severity.to_string(),
+        };
+        let mut output = format!(
+            "{}{}\n",
+            self.paint(severity_style, &severity),
+            self.paint(BOLD, &format!(": {}", diagnostic.message))
+        );
+
+        let (line, column, underline) = match &diagnostic.location {
+            Location::Span(span) => {
+                let (line, column) = self.source_map.line_column(span.start());
+                (Some(line), Some(column), self.underline_length(span, line))
+            }
+            Location::Unknown => (None, None, 0),
+        };
+        let text = line.and_then(|line| self.source_map.line_text(line));
+
+        // the gutter is as wide as the line number
+        let gutter = " ".repeat(line.map_or(1, |line| line.to_string().len()));
+        let bar = self.paint(BLUE, "|");
+
+        let position = match (line, column) {
+            (Some(line), Some(column)) => format!(":{}:{}", line, column),
+            (Some(line), None) => format!(":{}", line),
+            _ => String::new(),
+        };
+        output.push_str(&format!(
+            "{}{} {}{}\n",
+            gutter,
+            self.paint(BLUE, "-->"),
+            self.source_map.name(),
+            position
+        ));
+
+        if let (Some(line), Some(text)) = (line, text) {
+            output.push_str(&format!("{} {}\n", gutter, bar));
+            output.push_str(&format!(
+                "{} {} {}\n",
+                self.paint(BLUE, &line.to_string()),
+                bar,
+                text
+            ));
+            match column {
+                Some(column) => {
+                    let padding: String = text
+                        .chars()
+                        .take(column - 1)
+                        // keep tabs so the caret lines up with the source line
+                        .map(|c| if c == '\t' { '\t' } else { ' ' })
+                        .collect();
+                    let mut marker = format!("{}{}", padding, "^".repeat(underline.max(1)));
+                    if !diagnostic.label.is_empty() {
+                        marker.push(' ');
+                        marker.push_str(&diagnostic.label);
+                    }
+                    output.push_str(&format!(
+                        "{} {} {}\n",
+                        gutter,
+                        bar,
+                        self.paint(severity_style, &marker)
+                    ));
+                }
+                None if !diagnostic.label.is_empty() => {
+                    output.push_str(&format!("{} {} {}\n", gutter, bar, diagnostic.label));
+                }
+                None => {}
+            }
+        } else if !diagnostic.label.is_empty() {
+            output.push_str(&format!("{} {} {}\n", gutter, bar, diagnostic.label));
+        }
+
+        for note in &diagnostic.notes {
+            output.push_str(&format!(
+                "{} {} note: {}\n",
+                gutter,
+                self.paint(BLUE, "="),
+                note
+            ));
+        }
+        for help in &diagnostic.help {
+            output.push_str(&format!(
+                "{} {} help: {}\n",
+                gutter,
+                self.paint(BLUE, "="),
+                help
+            ));
+        }
+        output
+    }
+
+    /// How many characters of `line` the span covers, spans over several lines are cut at the line end
+    fn underline_length(&self, span: &Span, line: usize) -> usize {
+        let text = self.source_map.line_text(line).unwrap_or("");
+        let (_, column) = self.source_map.line_column(span.start());
+        let rest_of_line = text.chars().count().saturating_sub(column - 1);
+        let covered = self.source_map.snippet(span).map_or(0, |snippet| {
+            snippet.chars().take_while(|&c| c != '\n').count()
+        });
+        covered.min(rest_of_line)
+    }
+}
+
+/// A diagnostic resolved against its source, ready to be handed to other tools
+///
+/// Lines and columns count from 1, columns are in characters, `end_column` is exclusive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub label: String,
+    pub file: String,
+    /// byte offsets of the span
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub end_line: Option<usize>,
+    pub end_column: Option<usize>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+}
+
+impl Record {
+    pub fn new(source_map: &SourceMap, diagnostic: &Diagnostic) -> Self {
+        let mut record = Record {
+            severity: diagnostic.severity,
+            code: diagnostic.code,
+            message: diagnostic.message.clone(),
+            label: diagnostic.label.clone(),
+            file: source_map.name().to_string(),
+            start: None,
+            end: None,
+            line: None,
+            column: None,
+            end_line: None,
+            end_column: None,
+            notes: diagnostic.notes.clone(),
+            help: diagnostic.help.clone(),
+        };
+        match &diagnostic.location {
+            Location::Span(span) => {
+                let (line, column) = source_map.line_column(span.start());
+                let (end_line, end_column) = source_map.line_column(span.end());
+                record.start = Some(span.start());
+                record.end = Some(span.end());
+                record.line = Some(line);
+                record.column = Some(column);
+                record.end_line = Some(end_line);
+                record.end_column = Some(end_column);
+            }
+            Location::Unknown => {}
+        }
+        record
+    }
+
+    /// A single line JSON object, missing positions are `null`
+    ///
+    /// ```text
+    /// {"severity":"error","code":"E003","message":"Bad Addition","label":"","file":"a.lox",
+    ///  "start":45,"end":46,"line":6,"column":9,"end_line":6,"end_column":10,"notes":[],"help":[]}
+    /// ```
+    pub fn to_json(&self) -> String {
+        fn number(n: Option<usize>) -> String {
+            n.map_or("null".to_string(), |n| n.to_string())
+        }
+        fn list(items: &[String]) -> String {
+            let items = items
+                .iter()
+                .map(|item| json_string(item))
+                .collect::<Vec<_>>();
+            format!("[{}]", items.join(","))
+        }
+
+        format!(
+            "{{\"severity\":{},\"code\":{},\"message\":{},\"label\":{},\"file\":{},\
+             \"start\":{},\"end\":{},\"line\":{},\"column\":{},\"end_line\":{},\"end_column\":{},\
+             \"notes\":{},\"help\":{}}}",
+            json_string(&self.severity.to_string()),
+            self.code.map_or("null".to_string(), json_string),
+            json_string(&self.message),
+            json_string(&self.label),
+            json_string(&self.file),
+            number(self.start),
+            number(self.end),
+            number(self.line),
+            number(self.column),
+            number(self.end_line),
+            number(self.end_column),
+            list(&self.notes),
+            list(&self.help),
+        )
+    }
+}
+
+/// Quote and escape a string for JSON
+fn json_string(s: &str) -> String {
+    let mut json = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Somewhere to send diagnostics as they are found
+pub trait DiagnosticSink {
+    fn emit(&mut self, source_map: &SourceMap, diagnostic: &Diagnostic);
+
+    /// Emit every diagnostic for an error
+    fn emit_error(&mut self, source_map: &SourceMap, error: &LoxError) {
+        for diagnostic in Diagnostic::from_error(error) {
+            self.emit(source_map, &diagnostic);
+        }
+    }
+}
+
+/// Shares a sink, so that whoever created it can still look at it afterwards
+impl<T: DiagnosticSink> DiagnosticSink for Rc<RefCell<T>> {
+    fn emit(&mut self, source_map: &SourceMap, diagnostic: &Diagnostic) {
+        self.borrow_mut().emit(source_map, diagnostic);
+    }
+}
+
+/// Renders diagnostics to stderr for people to read
+#[derive(Default)]
+pub struct HumanSink {
+    pub colour: bool,
+}
+
+impl HumanSink {
+    pub fn new(colour: bool) -> Self {
+        HumanSink { colour }
+    }
+}
+
+impl DiagnosticSink for HumanSink {
+    fn emit(&mut self, source_map: &SourceMap, diagnostic: &Diagnostic) {
+        eprint!(
+            "{}",
+            Renderer::new(source_map)
+                .with_colour(self.colour)
+                .render(diagnostic)
+        );
+    }
+}
+
+/// Writes one JSON `Record` per line, for tools such as CI annotations
+pub struct JsonSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonSink<W> {
+    pub fn new(writer: W) -> Self {
+        JsonSink { writer }
+    }
+}
+
+impl<W: Write> DiagnosticSink for JsonSink<W> {
+    fn emit(&mut self, source_map: &SourceMap, diagnostic: &Diagnostic) {
+        let record = Record::new(source_map, diagnostic);
+        // diagnostics are best effort, there is nowhere left to report a failure to write them
+        let _ = writeln!(self.writer, "{}", record.to_json());
+    }
+}
+
+/// Keeps every diagnostic as a `Record`
+#[derive(Default)]
+pub struct CollectingSink {
+    pub records: Vec<Record>,
+}
+
+impl CollectingSink {
+    pub fn new() -> Self {
+        CollectingSink {
+            records: Vec::new(),
+        }
+    }
+}
+
+impl DiagnosticSink for CollectingSink {
+    fn emit(&mut self, source_map: &SourceMap, diagnostic: &Diagnostic) {
+        self.records.push(Record::new(source_map, diagnostic));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CollectingSink, Diagnostic, DiagnosticSink, JsonSink, Location, Renderer};
+    use crate::{lex::SourceMap, Backend, Interpreter};
+
+    #[test]
+    fn test_render_runtime_error() {
+        let source = "var a = 1;\nprint a + nil;\n";
+        let error = Interpreter::new().run(source).unwrap_err();
+        let map = SourceMap::new("test.lox", source);
+
+        assert_eq!(
+            "error[E003]: Bad Addition\n --> test.lox:2:9\n  |\n2 | print a + nil;\n  |         ^\n",
+            Renderer::new(&map).render_error(&error)
+        );
+    }
+
+    #[test]
+    fn test_backends_render_runtime_errors_the_same() {
+        let sources = [
+            "var a = 1;\nprint a + nil;\n",
+            "print -\"a\";",
+            "fun f(a) {}\n  f(1, 2);",
+            "class A {}\nprint A().missing;",
+            "print undefined;",
+        ];
+        for source in sources.iter() {
+            let map = SourceMap::new("test.lox", *source);
+            let tree_walk = Interpreter::with_backend(Backend::TreeWalk)
+                .run(*source)
+                .unwrap_err();
+            let bytecode = Interpreter::with_backend(Backend::Bytecode)
+                .run(*source)
+                .unwrap_err();
+            assert_eq!(
+                Renderer::new(&map).render_error(&tree_walk),
+                Renderer::new(&map).render_error(&bytecode)
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_underline_and_notes() {
+        let source = "var name = nope;";
+        let error = Interpreter::new().run(source).unwrap_err();
+        let map = SourceMap::new("test.lox", source);
+
+        let rendered = Renderer::new(&map).render_error(&error);
+        assert!(rendered.contains("1 | var name = nope;\n  |            ^^^^\n"));
+        assert!(rendered.contains("= help: declare it with `var` before using it"));
+    }
+
+    #[test]
+    fn test_render_without_location() {
+        let map = SourceMap::new("<repl>", "");
+        let diagnostic = Diagnostic::error("Oops", Location::Unknown).with_note("a note");
+
+        assert_eq!(
+            "error: Oops\n --> <repl>\n  = note: a note\n",
+            Renderer::new(&map).render(&diagnostic)
+        );
+    }
+
+    #[test]
+    fn test_render_with_colour() {
+        let map = SourceMap::new("<repl>", "");
+        let diagnostic = Diagnostic::error("Oops", Location::Unknown);
+
+        let rendered = Renderer::new(&map).with_colour(true).render(&diagnostic);
+        assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m"));
+    }
+
+    #[test]
+    fn test_records_as_json() {
+        let source = "var a = 1;\nprint a + \"\\t\" + nil;\n";
+        let error = Interpreter::new().run(source).unwrap_err();
+        let map = SourceMap::new("dir/test.lox", source);
+
+        let mut sink = JsonSink::new(Vec::new());
+        sink.emit_error(&map, &error);
+        let json = String::from_utf8(sink.writer).unwrap();
+
+        assert_eq!(
+            "{\"severity\":\"error\",\"code\":\"E003\",\"message\":\"Bad Addition\",\"label\":\"\",\
+             \"file\":\"dir/test.lox\",\"start\":26,\"end\":27,\"line\":2,\"column\":16,\
+             \"end_line\":2,\"end_column\":17,\"notes\":[],\"help\":[]}\n",
+            json
+        );
+    }
+
+    #[test]
+    fn test_collect_every_error() {
+        let source = "print ;\nvar = 1;";
+        let error = Interpreter::new().run(source).unwrap_err();
+        let map = SourceMap::new("test.lox", source);
+
+        let mut sink = CollectingSink::new();
+        sink.emit_error(&map, &error);
+
+        let codes = sink.records.iter().map(|r| r.code).collect::<Vec<_>>();
+        assert_eq!(vec![Some("P002"), Some("P010")], codes);
+        assert_eq!(Some(2), sink.records[1].line);
+    }
+}
@@ -1,21 +1,46 @@
+use std::sync::Arc;
+
 use thiserror::Error;
 
-use crate::{evaluate, lex, parse};
+use crate::{compile, evaluate, lex, parse, resolve, vm};
 
+/// Any error from lexing through to running a script
+///
+/// The errors that point at a token keep it in a `Box`, tokens are big now that spans carry
+/// byte offsets and every `Result` in the parser and the backends would grow with them.
 #[derive(Clone, Error, Debug)]
 pub enum LoxError {
     #[error(transparent)]
     LexerError(#[from] lex::Error),
+    #[error("{}", display_all(.0))]
+    LexerErrors(Vec<lex::Error>),
     #[error(transparent)]
     ParserError(#[from] parse::Error),
+    #[error("{}", display_all(.0))]
+    ParserErrors(Vec<parse::Error>),
+    #[error("{}", display_all(.0))]
+    ResolverErrors(Vec<resolve::Error>),
+    #[error(transparent)]
+    CompileError(#[from] compile::Error),
     #[error(transparent)]
     RuntimeError(#[from] RuntimeError),
+    /// A script couldn't be read, shared so that the error can still be cloned
+    #[error(transparent)]
+    IoError(Arc<std::io::Error>),
 }
 
 #[derive(Clone, Error, Debug)]
 pub enum RuntimeError {
     #[error(transparent)]
     EvaluationError(#[from] evaluate::Error),
+    #[error(transparent)]
+    VmError(#[from] vm::Error),
+}
+
+impl From<std::io::Error> for LoxError {
+    fn from(error: std::io::Error) -> Self {
+        LoxError::IoError(Arc::new(error))
+    }
 }
 
 impl From<evaluate::Error> for LoxError {
@@ -23,3 +48,18 @@ impl From<evaluate::Error> for LoxError {
         LoxError::RuntimeError(RuntimeError::EvaluationError(error))
     }
 }
+
+impl From<vm::Error> for LoxError {
+    fn from(error: vm::Error) -> Self {
+        LoxError::RuntimeError(RuntimeError::VmError(error))
+    }
+}
+
+/// One error per line
+fn display_all(errors: &[impl std::fmt::Display]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,76 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::ast::Function;
+use crate::evaluate::{Environment, Error, Evaluator, Flow, LoxInstance, Value};
+
+/// Anything that can be called with a list of arguments
+pub trait Callable {
+    /// The number of arguments the callable expects
+    fn arity(&self) -> usize;
+    fn call(&self, evaluator: &Evaluator, arguments: Vec<Value>) -> Result<Value, Error>;
+}
+
+/// A function declared in Lox code, along with the environment it was declared in
+#[derive(Debug)]
+pub struct LoxFunction {
+    declaration: Rc<Function>,
+    closure: Rc<RefCell<Environment>>,
+    /// initializers always return `this`
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Function,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        LoxFunction {
+            declaration: Rc::new(declaration),
+            closure,
+            is_initializer,
+        }
+    }
+
+    /// Create a copy of this method where `this` refers to the given instance
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        environment.define("this", Value::Instance(instance));
+        LoxFunction {
+            declaration: Rc::clone(&self.declaration),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+        }
+    }
+}
+
+impl Callable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(&self, evaluator: &Evaluator, arguments: Vec<Value>) -> Result<Value, Error> {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(param.name(), argument);
+        }
+
+        let flow = evaluator.execute_block(self.declaration.body.clone(), environment)?;
+        if self.is_initializer {
+            // unwrap: initializers are always bound to an instance
+            return Ok(self.closure.borrow().lookup("this").unwrap());
+        }
+
+        match flow {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(Value::Nil),
+        }
+    }
+}
+
+impl std::fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.name())
+    }
+}
@@ -0,0 +1,112 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::evaluate::{Callable, Error, ErrorKind, Evaluator, LoxFunction, Value};
+use crate::lex::Token;
+
+/// A class declared in Lox code
+#[derive(Debug)]
+pub struct LoxClass {
+    name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<String, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: impl Into<String>,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<String, Rc<LoxFunction>>,
+    ) -> Self {
+        LoxClass {
+            name: name.into(),
+            superclass,
+            methods,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Look up a method on this class, falling back to the superclass chain
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self
+                .superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+}
+
+/// Calling a class constructs a new instance of it
+impl Callable for Rc<LoxClass> {
+    fn arity(&self) -> usize {
+        self.find_method("init")
+            .map_or(0, |initializer| initializer.arity())
+    }
+
+    fn call(&self, evaluator: &Evaluator, arguments: Vec<Value>) -> Result<Value, Error> {
+        let instance = Rc::new(RefCell::new(LoxInstance::new(Rc::clone(self))));
+        if let Some(initializer) = self.find_method("init") {
+            initializer
+                .bind(Rc::clone(&instance))
+                .call(evaluator, arguments)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+}
+
+impl std::fmt::Display for LoxClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// An instance of a `LoxClass`, holding its own fields
+#[derive(Debug)]
+pub struct LoxInstance {
+    class: Rc<LoxClass>,
+    fields: HashMap<String, Value>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        LoxInstance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Get a field, or a method bound to this instance
+    ///
+    /// Fields shadow methods of the same name.
+    pub fn get(instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> Result<Value, Error> {
+        if let Some(value) = instance.borrow().fields.get(name.name()) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(name.name());
+        match method {
+            Some(method) => Ok(Value::Function(Rc::new(method.bind(Rc::clone(instance))))),
+            None => Err(Error::tokened(
+                "",
+                name.clone(),
+                ErrorKind::UndefinedProperty(name.name().to_string()),
+            )),
+        }
+    }
+
+    pub fn set(&mut self, name: &Token, value: Value) {
+        self.fields.insert(name.name().to_string(), value);
+    }
+}
+
+impl std::fmt::Display for LoxInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
@@ -0,0 +1,126 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::evaluate::{Error, ErrorKind, Value};
+use crate::lex::Token;
+
+/// Stores the values bound to variable names
+///
+/// Environments form a chain through `enclosing`, inner scopes shadow outer ones
+/// and lookups walk outwards until the name is found or the global scope is exhausted.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing: None,
+        }
+    }
+
+    /// Create a new inner scope
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// Bind a name to a value in this scope, redefining an existing variable is allowed
+    pub fn define(&mut self, name: impl Into<String>, value: Value) {
+        self.values.insert(name.into(), value);
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, Error> {
+        self.lookup(name.name()).ok_or_else(|| {
+            Error::tokened(
+                "",
+                name.clone(),
+                ErrorKind::UndefinedVariable(name.name().to_string()),
+            )
+        })
+    }
+
+    /// Find the value of a variable by name, walking out through the enclosing scopes
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        match self.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self
+                .enclosing
+                .as_ref()
+                .and_then(|enclosing| enclosing.borrow().lookup(name)),
+        }
+    }
+
+    /// Assign a new value to an existing variable, unlike `define` the variable must already exist
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), Error> {
+        match self.values.get_mut(name.name()) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+                None => Err(Error::tokened(
+                    "",
+                    name.clone(),
+                    ErrorKind::UndefinedVariable(name.name().to_string()),
+                )),
+            },
+        }
+    }
+
+    /// Walk `distance` scopes outwards, the resolver guarantees the scope exists
+    pub fn ancestor(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+    ) -> Rc<RefCell<Environment>> {
+        let mut environment = Rc::clone(environment);
+        for _ in 0..distance {
+            let enclosing = environment
+                .borrow()
+                .enclosing
+                .clone()
+                .expect("resolved scope depth should exist");
+            environment = enclosing;
+        }
+        environment
+    }
+
+    /// Get a variable that the resolver found `distance` scopes away
+    pub fn get_at(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+        name: &Token,
+    ) -> Result<Value, Error> {
+        Environment::ancestor(environment, distance)
+            .borrow()
+            .values
+            .get(name.name())
+            .cloned()
+            .ok_or_else(|| {
+                Error::tokened(
+                    "",
+                    name.clone(),
+                    ErrorKind::UndefinedVariable(name.name().to_string()),
+                )
+            })
+    }
+
+    /// Assign to a variable that the resolver found `distance` scopes away
+    pub fn assign_at(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+        name: &Token,
+        value: Value,
+    ) -> Result<(), Error> {
+        Environment::ancestor(environment, distance)
+            .borrow_mut()
+            .assign(name, value)
+    }
+}
@@ -5,7 +5,7 @@ use crate::lex::Token;
 #[derive(Clone, Error, Debug)]
 #[error("{kind} Occurred at {token} {message}")]
 pub struct Error {
-    token: Token,
+    token: Box<Token>,
     kind: ErrorKind,
     message: String,
 }
@@ -20,6 +20,14 @@ pub enum ErrorKind {
     BadDivision,
     BadStringRepCount,
     BadComparison,
+    UndefinedVariable(String),
+    NotCallable,
+    ArityMismatch { expected: usize, found: usize },
+    UndefinedProperty(String),
+    OnlyInstancesHaveProperties,
+    SuperclassMustBeClass,
+    NativeFunctionError,
+    StackOverflow,
 }
 
 impl Error {
@@ -27,7 +35,42 @@ impl Error {
         Error {
             kind,
             message: msg.into(),
-            token,
+            token: Box::new(token),
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl ErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::BadNumericalNegation => "E001",
+            ErrorKind::BadBooleanNegation => "E002",
+            ErrorKind::BadAddition => "E003",
+            ErrorKind::BadSubtraction => "E004",
+            ErrorKind::BadMultiplication => "E005",
+            ErrorKind::BadDivision => "E006",
+            ErrorKind::BadStringRepCount => "E007",
+            ErrorKind::BadComparison => "E008",
+            ErrorKind::UndefinedVariable(_) => "E009",
+            ErrorKind::NotCallable => "E010",
+            ErrorKind::ArityMismatch { .. } => "E011",
+            ErrorKind::UndefinedProperty(_) => "E012",
+            ErrorKind::OnlyInstancesHaveProperties => "E013",
+            ErrorKind::SuperclassMustBeClass => "E014",
+            ErrorKind::NativeFunctionError => "E015",
+            ErrorKind::StackOverflow => "E016",
         }
     }
 }
@@ -45,6 +88,16 @@ impl std::fmt::Display for ErrorKind {
                 write!(f, "Bad count for string repitition, expected an integer")
             }
             ErrorKind::BadComparison => write!(f, "Bad Comparison"),
+            ErrorKind::UndefinedVariable(name) => write!(f, "Undefined variable `{}`", name),
+            ErrorKind::NotCallable => write!(f, "Value is not callable"),
+            ErrorKind::ArityMismatch { expected, found } => {
+                write!(f, "Expected {} arguments but got {}", expected, found)
+            }
+            ErrorKind::UndefinedProperty(name) => write!(f, "Undefined property `{}`", name),
+            ErrorKind::OnlyInstancesHaveProperties => write!(f, "Only instances have properties"),
+            ErrorKind::SuperclassMustBeClass => write!(f, "Superclass must be a class"),
+            ErrorKind::NativeFunctionError => write!(f, "Error in native function"),
+            ErrorKind::StackOverflow => write!(f, "Stack overflow"),
         }
     }
 }
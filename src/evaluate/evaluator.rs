@@ -1,29 +1,240 @@
-use crate::ast::*;
-use crate::evaluate::{Error, ErrorKind};
-use crate::lex::TokenKind;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::{visitor::OwnedStmtVisitor, *};
+use crate::evaluate::{
+    Callable, Environment, Error, ErrorKind, LoxClass, LoxFunction, LoxInstance, Value,
+};
+use crate::interpreter::MAX_CALL_DEPTH;
+use crate::lex::{Token, TokenKind};
+use crate::resolve::Locals;
 use crate::OwnedVisitor;
 
-pub fn is_truthy(value: LiteralValue) -> bool {
-    match value {
-        LiteralValue::Bool(false) => false,
-        LiteralValue::Nil => false,
-        _ => true,
-    }
+/// How control leaves a statement once it has been executed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Flow {
+    /// Carry on with the next statement
+    Next,
+    /// Leave the innermost loop
+    Break,
+    /// Skip to the next iteration of the innermost loop
+    Continue,
+    /// Leave the current function call with a value
+    Return(Value),
 }
 
-pub struct Evaluator;
+pub struct Evaluator {
+    /// The outermost scope
+    globals: Rc<RefCell<Environment>>,
+    /// The innermost scope, swapped out whenever a block is entered or exited
+    environment: RefCell<Rc<RefCell<Environment>>>,
+    /// Scope depths of local variables, as found by the `Resolver`
+    locals: RefCell<Locals>,
+    /// How many Lox functions are being called right now, at most `MAX_CALL_DEPTH`
+    call_depth: Cell<usize>,
+}
 
 impl<'a> Evaluator {
-    pub fn evaluate(&self, expr: Expr) -> Result<LiteralValue, Error> {
+    pub fn new() -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        Evaluator {
+            environment: RefCell::new(Rc::clone(&globals)),
+            globals,
+            locals: RefCell::new(Locals::new()),
+            call_depth: Cell::new(0),
+        }
+    }
+
+    /// Remember where local variables live, this must be called before executing resolved statements
+    pub fn resolve(&self, locals: Locals) {
+        self.locals.borrow_mut().extend(locals);
+    }
+
+    fn look_up_variable(&self, name: &Token, id: ExprId) -> Result<Value, Error> {
+        match self.locals.borrow().get(&id) {
+            Some(&distance) => Environment::get_at(&self.environment(), distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    /// Define a global variable, e.g. to expose a `Value::NativeFunction` to Lox code
+    pub fn define_global(&self, name: impl Into<String>, value: impl Into<Value>) {
+        self.globals.borrow_mut().define(name, value.into());
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().lookup(name)
+    }
+
+    fn environment(&self) -> Rc<RefCell<Environment>> {
+        Rc::clone(&self.environment.borrow())
+    }
+
+    /// Execute statements inside the given scope, restoring the current scope afterwards
+    ///
+    /// Execution stops early if a statement wants to leave the block, e.g. `break`.
+    pub fn execute_block(
+        &self,
+        statements: Vec<Stmt>,
+        environment: Environment,
+    ) -> Result<Flow, Error> {
+        let previous = self.environment.replace(Rc::new(RefCell::new(environment)));
+
+        let mut result = Ok(Flow::Next);
+        for statement in statements {
+            result = self.visit_stmt(statement);
+            match result {
+                Ok(Flow::Next) => continue,
+                _ => break,
+            }
+        }
+
+        self.environment.replace(previous);
+        result
+    }
+
+    pub fn evaluate(&self, expr: Expr) -> Result<Value, Error> {
         self.visit_expr(expr)
     }
+
+    pub fn execute(&self, stmt: Stmt) -> Result<(), Error> {
+        // the parser guarantees that loop control statements only appear inside loops,
+        // so any `Flow` has been handled by the time it reaches the top level
+        self.visit_stmt(stmt)?;
+        Ok(())
+    }
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator::new()
+    }
 }
 
-impl OwnedVisitor<Result<LiteralValue, Error>> for Evaluator {
-    fn visit_grouping(&self, grouping: Grouping) -> Result<LiteralValue, Error> {
+impl OwnedStmtVisitor<Result<Flow, Error>> for Evaluator {
+    fn visit_expr_stmt(&self, stmt: ExprStmt) -> Result<Flow, Error> {
+        self.evaluate(stmt.0)?;
+        Ok(Flow::Next)
+    }
+    fn visit_print_stmt(&self, stmt: PrintStmt) -> Result<Flow, Error> {
+        let value = self.evaluate(stmt.0)?;
+        println!("{}", value);
+        Ok(Flow::Next)
+    }
+    fn visit_var_stmt(&self, stmt: Var) -> Result<Flow, Error> {
+        let value = match stmt.initializer {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => Value::Nil,
+        };
+        self.environment()
+            .borrow_mut()
+            .define(stmt.name.name(), value);
+        Ok(Flow::Next)
+    }
+    fn visit_block(&self, block: Block) -> Result<Flow, Error> {
+        self.execute_block(
+            block.statements,
+            Environment::with_enclosing(self.environment()),
+        )
+    }
+    fn visit_if(&self, stmt: If) -> Result<Flow, Error> {
+        if self.evaluate(stmt.condition)?.is_truthy() {
+            self.visit_stmt(*stmt.then_branch)
+        } else if let Some(else_branch) = stmt.else_branch {
+            self.visit_stmt(*else_branch)
+        } else {
+            Ok(Flow::Next)
+        }
+    }
+    fn visit_while(&self, stmt: While) -> Result<Flow, Error> {
+        while self.evaluate(stmt.condition.clone())?.is_truthy() {
+            match self.visit_stmt(*stmt.body.clone())? {
+                Flow::Break => break,
+                Flow::Return(value) => return Ok(Flow::Return(value)),
+                Flow::Next | Flow::Continue => {}
+            }
+            if let Some(increment) = &stmt.increment {
+                self.evaluate(increment.clone())?;
+            }
+        }
+        Ok(Flow::Next)
+    }
+    fn visit_break(&self, _stmt: Break) -> Result<Flow, Error> {
+        Ok(Flow::Break)
+    }
+    fn visit_continue(&self, _stmt: Continue) -> Result<Flow, Error> {
+        Ok(Flow::Continue)
+    }
+    fn visit_function(&self, stmt: Function) -> Result<Flow, Error> {
+        let name = stmt.name.name().to_string();
+        let function = LoxFunction::new(stmt, self.environment(), false);
+        self.environment()
+            .borrow_mut()
+            .define(name, Value::Function(Rc::new(function)));
+        Ok(Flow::Next)
+    }
+    fn visit_return(&self, stmt: Return) -> Result<Flow, Error> {
+        let value = match stmt.value {
+            Some(value) => self.evaluate(value)?,
+            None => Value::Nil,
+        };
+        Ok(Flow::Return(value))
+    }
+    fn visit_class(&self, stmt: Class) -> Result<Flow, Error> {
+        let superclass = match stmt.superclass {
+            Some(superclass) => match self.visit_variable(superclass.clone())? {
+                Value::Class(class) => Some(class),
+                _ => {
+                    return Err(Error::tokened(
+                        "",
+                        superclass.name,
+                        ErrorKind::SuperclassMustBeClass,
+                    ))
+                }
+            },
+            None => None,
+        };
+
+        let name = stmt.name.name().to_string();
+        self.environment()
+            .borrow_mut()
+            .define(name.clone(), Value::Nil);
+
+        // methods of a subclass close over an extra scope holding `super`
+        let method_closure = match &superclass {
+            Some(superclass) => {
+                let mut environment = Environment::with_enclosing(self.environment());
+                environment.define("super", Value::Class(Rc::clone(superclass)));
+                Rc::new(RefCell::new(environment))
+            }
+            None => self.environment(),
+        };
+
+        let methods: HashMap<String, Rc<LoxFunction>> = stmt
+            .methods
+            .into_iter()
+            .map(|method| {
+                let name = method.name.name().to_string();
+                let is_initializer = name == "init";
+                let function = LoxFunction::new(method, Rc::clone(&method_closure), is_initializer);
+                (name, Rc::new(function))
+            })
+            .collect();
+
+        let class = LoxClass::new(name, superclass, methods);
+        self.environment()
+            .borrow_mut()
+            .assign(&stmt.name, Value::Class(Rc::new(class)))?;
+        Ok(Flow::Next)
+    }
+}
+
+impl OwnedVisitor<Result<Value, Error>> for Evaluator {
+    fn visit_grouping(&self, grouping: Grouping) -> Result<Value, Error> {
         self.evaluate(*grouping.0)
     }
-    fn visit_binary(&self, binary: Binary) -> Result<LiteralValue, Error> {
+    fn visit_binary(&self, binary: Binary) -> Result<Value, Error> {
         let left_value = self.evaluate(*binary.left)?;
         let right_value = self.evaluate(*binary.right)?;
         match binary.operator.kind {
@@ -31,33 +242,31 @@ impl OwnedVisitor<Result<LiteralValue, Error>> for Evaluator {
             // Addition
             //
             TokenKind::PLUS => match (left_value, right_value) {
-                (LiteralValue::Number(l), LiteralValue::Number(r)) => {
-                    Ok(LiteralValue::Number(l + r))
-                }
+                (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l + r)),
                 // concatenate strings
-                (LiteralValue::String(mut l), LiteralValue::String(r)) => {
+                (Value::String(mut l), Value::String(r)) => {
                     l.push_str(&r);
-                    Ok(LiteralValue::String(l))
+                    Ok(Value::String(l))
                 }
-                (LiteralValue::String(l), LiteralValue::Number(r)) => {
-                    Ok(LiteralValue::String(format!("{}{}", l, r)))
+                (Value::String(l), Value::Number(r)) => {
+                    Ok(Value::String(format!("{}{}", l, format_number(r))))
                 }
-                (LiteralValue::Number(l), LiteralValue::String(r)) => {
-                    Ok(LiteralValue::String(format!("{}{}", l, r)))
+                (Value::Number(l), Value::String(r)) => {
+                    Ok(Value::String(format!("{}{}", format_number(l), r)))
                 }
                 // cast booleans as 0 or 1
-                (LiteralValue::Number(l), LiteralValue::Bool(x)) => {
+                (Value::Number(l), Value::Bool(x)) => {
                     let r = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Number(l + r))
+                    Ok(Value::Number(l + r))
                 }
-                (LiteralValue::Bool(x), LiteralValue::Number(r)) => {
+                (Value::Bool(x), Value::Number(r)) => {
                     let l = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Number(l + r))
+                    Ok(Value::Number(l + r))
                 }
-                (LiteralValue::Bool(l), LiteralValue::Bool(r)) => {
+                (Value::Bool(l), Value::Bool(r)) => {
                     let l = if l { 1.0 } else { 0.0 };
                     let r = if r { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Number(l + r))
+                    Ok(Value::Number(l + r))
                 }
                 _ => Err(Error::tokened("", binary.operator, ErrorKind::BadAddition)),
             },
@@ -65,22 +274,20 @@ impl OwnedVisitor<Result<LiteralValue, Error>> for Evaluator {
             // Subtraction
             //
             TokenKind::MINUS => match (left_value, right_value) {
-                (LiteralValue::Number(l), LiteralValue::Number(r)) => {
-                    Ok(LiteralValue::Number(l - r))
-                }
+                (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l - r)),
                 // cast booleans as 0 or 1
-                (LiteralValue::Number(l), LiteralValue::Bool(x)) => {
+                (Value::Number(l), Value::Bool(x)) => {
                     let r = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Number(l - r))
+                    Ok(Value::Number(l - r))
                 }
-                (LiteralValue::Bool(x), LiteralValue::Number(r)) => {
+                (Value::Bool(x), Value::Number(r)) => {
                     let l = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Number(l - r))
+                    Ok(Value::Number(l - r))
                 }
-                (LiteralValue::Bool(l), LiteralValue::Bool(r)) => {
+                (Value::Bool(l), Value::Bool(r)) => {
                     let l = if l { 1.0 } else { 0.0 };
                     let r = if r { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Number(l - r))
+                    Ok(Value::Number(l - r))
                 }
                 _ => Err(Error::tokened(
                     "",
@@ -92,11 +299,9 @@ impl OwnedVisitor<Result<LiteralValue, Error>> for Evaluator {
             // Multiplication
             //
             TokenKind::STAR => match (left_value, right_value) {
-                (LiteralValue::Number(l), LiteralValue::Number(r)) => {
-                    Ok(LiteralValue::Number(l * r))
-                }
+                (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l * r)),
                 // string repitition
-                (LiteralValue::String(l), LiteralValue::Number(r)) => {
+                (Value::String(l), Value::Number(r)) => {
                     let mut word = String::new();
                     // ignore fractional parts of a number OR error?
                     let count = r.trunc() as usize;
@@ -111,48 +316,46 @@ impl OwnedVisitor<Result<LiteralValue, Error>> for Evaluator {
                     for _ in 0..count {
                         word.push_str(&l);
                     }
-                    Ok(LiteralValue::String(word))
+                    Ok(Value::String(word))
                 }
                 // cast booleans as 0 or 1
-                (LiteralValue::Number(l), LiteralValue::Bool(x)) => {
+                (Value::Number(l), Value::Bool(x)) => {
                     let r = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Number(l * r))
+                    Ok(Value::Number(l * r))
                 }
-                (LiteralValue::Bool(x), LiteralValue::Number(r)) => {
+                (Value::Bool(x), Value::Number(r)) => {
                     let l = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Number(l * r))
+                    Ok(Value::Number(l * r))
                 }
-                (LiteralValue::Bool(l), LiteralValue::Bool(r)) => {
+                (Value::Bool(l), Value::Bool(r)) => {
                     let l = if l { 1.0 } else { 0.0 };
                     let r = if r { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Number(l * r))
+                    Ok(Value::Number(l * r))
                 }
                 _ => Err(Error::tokened(
                     "",
                     binary.operator,
-                    ErrorKind::BadSubtraction,
+                    ErrorKind::BadMultiplication,
                 )),
             },
             //
             // Division
             //
             TokenKind::SLASH => match (left_value, right_value) {
-                (LiteralValue::Number(l), LiteralValue::Number(r)) => {
-                    Ok(LiteralValue::Number(l / r))
-                }
+                (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l / r)),
                 // cast booleans as 0 or 1
-                (LiteralValue::Number(l), LiteralValue::Bool(x)) => {
+                (Value::Number(l), Value::Bool(x)) => {
                     let r = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Number(l / r))
+                    Ok(Value::Number(l / r))
                 }
-                (LiteralValue::Bool(x), LiteralValue::Number(r)) => {
+                (Value::Bool(x), Value::Number(r)) => {
                     let l = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Number(l / r))
+                    Ok(Value::Number(l / r))
                 }
-                (LiteralValue::Bool(l), LiteralValue::Bool(r)) => {
+                (Value::Bool(l), Value::Bool(r)) => {
                     let l = if l { 1.0 } else { 0.0 };
                     let r = if r { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Number(l / r))
+                    Ok(Value::Number(l / r))
                 }
                 _ => Err(Error::tokened("", binary.operator, ErrorKind::BadDivision)),
             },
@@ -160,20 +363,20 @@ impl OwnedVisitor<Result<LiteralValue, Error>> for Evaluator {
             // Comparisons
             //
             TokenKind::GREATER => match (left_value, right_value) {
-                (LiteralValue::Number(l), LiteralValue::Number(r)) => Ok(LiteralValue::Bool(l > r)),
+                (Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l > r)),
                 // cast booleans as 0 or 1
-                (LiteralValue::Number(l), LiteralValue::Bool(x)) => {
+                (Value::Number(l), Value::Bool(x)) => {
                     let r = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Bool(l > r))
+                    Ok(Value::Bool(l > r))
                 }
-                (LiteralValue::Bool(x), LiteralValue::Number(r)) => {
+                (Value::Bool(x), Value::Number(r)) => {
                     let l = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Bool(l > r))
+                    Ok(Value::Bool(l > r))
                 }
-                (LiteralValue::Bool(l), LiteralValue::Bool(r)) => {
+                (Value::Bool(l), Value::Bool(r)) => {
                     let l = if l { 1.0 } else { 0.0 };
                     let r = if r { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Bool(l > r))
+                    Ok(Value::Bool(l > r))
                 }
                 _ => Err(Error::tokened(
                     "",
@@ -182,20 +385,20 @@ impl OwnedVisitor<Result<LiteralValue, Error>> for Evaluator {
                 )),
             },
             TokenKind::GREATER_EQUAL => match (left_value, right_value) {
-                (LiteralValue::Number(l), LiteralValue::Number(r)) => Ok(LiteralValue::Bool(l > r)),
+                (Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l >= r)),
                 // cast booleans as 0 or 1
-                (LiteralValue::Number(l), LiteralValue::Bool(x)) => {
+                (Value::Number(l), Value::Bool(x)) => {
                     let r = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Bool(l >= r))
+                    Ok(Value::Bool(l >= r))
                 }
-                (LiteralValue::Bool(x), LiteralValue::Number(r)) => {
+                (Value::Bool(x), Value::Number(r)) => {
                     let l = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Bool(l >= r))
+                    Ok(Value::Bool(l >= r))
                 }
-                (LiteralValue::Bool(l), LiteralValue::Bool(r)) => {
+                (Value::Bool(l), Value::Bool(r)) => {
                     let l = if l { 1.0 } else { 0.0 };
                     let r = if r { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Bool(l >= r))
+                    Ok(Value::Bool(l >= r))
                 }
                 _ => Err(Error::tokened(
                     "",
@@ -204,20 +407,20 @@ impl OwnedVisitor<Result<LiteralValue, Error>> for Evaluator {
                 )),
             },
             TokenKind::LESS => match (left_value, right_value) {
-                (LiteralValue::Number(l), LiteralValue::Number(r)) => Ok(LiteralValue::Bool(l > r)),
+                (Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l < r)),
                 // cast booleans as 0 or 1
-                (LiteralValue::Number(l), LiteralValue::Bool(x)) => {
+                (Value::Number(l), Value::Bool(x)) => {
                     let r = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Bool(l < r))
+                    Ok(Value::Bool(l < r))
                 }
-                (LiteralValue::Bool(x), LiteralValue::Number(r)) => {
+                (Value::Bool(x), Value::Number(r)) => {
                     let l = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Bool(l < r))
+                    Ok(Value::Bool(l < r))
                 }
-                (LiteralValue::Bool(l), LiteralValue::Bool(r)) => {
+                (Value::Bool(l), Value::Bool(r)) => {
                     let l = if l { 1.0 } else { 0.0 };
                     let r = if r { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Bool(l < r))
+                    Ok(Value::Bool(l < r))
                 }
                 _ => Err(Error::tokened(
                     "",
@@ -226,20 +429,20 @@ impl OwnedVisitor<Result<LiteralValue, Error>> for Evaluator {
                 )),
             },
             TokenKind::LESS_EQUAL => match (left_value, right_value) {
-                (LiteralValue::Number(l), LiteralValue::Number(r)) => Ok(LiteralValue::Bool(l > r)),
+                (Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l <= r)),
                 // cast booleans as 0 or 1
-                (LiteralValue::Number(l), LiteralValue::Bool(x)) => {
+                (Value::Number(l), Value::Bool(x)) => {
                     let r = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Bool(l <= r))
+                    Ok(Value::Bool(l <= r))
                 }
-                (LiteralValue::Bool(x), LiteralValue::Number(r)) => {
+                (Value::Bool(x), Value::Number(r)) => {
                     let l = if x { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Bool(l <= r))
+                    Ok(Value::Bool(l <= r))
                 }
-                (LiteralValue::Bool(l), LiteralValue::Bool(r)) => {
+                (Value::Bool(l), Value::Bool(r)) => {
                     let l = if l { 1.0 } else { 0.0 };
                     let r = if r { 1.0 } else { 0.0 };
-                    Ok(LiteralValue::Bool(l <= r))
+                    Ok(Value::Bool(l <= r))
                 }
                 _ => Err(Error::tokened(
                     "",
@@ -247,28 +450,182 @@ impl OwnedVisitor<Result<LiteralValue, Error>> for Evaluator {
                     ErrorKind::BadComparison,
                 )),
             },
+            //
+            // Equality
+            //
+            TokenKind::EQUAL_EQUAL => Ok(Value::Bool(left_value == right_value)),
+            TokenKind::BANG_EQUAL => Ok(Value::Bool(left_value != right_value)),
             _ => unreachable!(
                 "cannot evaluate this token here in a binary expression, bad input to evaluator"
             ),
         }
     }
-    fn visit_unary(&self, unary: Unary) -> Result<LiteralValue, Error> {
+    fn visit_unary(&self, unary: Unary) -> Result<Value, Error> {
         let value = self.evaluate(*unary.right)?;
         match (unary.operator.kind, value) {
-            (TokenKind::MINUS, LiteralValue::Number(n)) => Ok(LiteralValue::Number(-n)),
+            (TokenKind::MINUS, Value::Number(n)) => Ok(Value::Number(-n)),
             (TokenKind::MINUS, _) => Err(Error::tokened(
                 "",
                 unary.operator,
                 ErrorKind::BadNumericalNegation,
             )),
-            (TokenKind::BANG, value) => Ok(LiteralValue::Bool(!is_truthy(value))),
+            (TokenKind::BANG, value) => Ok(Value::Bool(!value.is_truthy())),
             _ => unreachable!(
                 "cannot evaluate this token here in a unary expression, bad input to evaluator"
             ),
         }
     }
-    fn visit_literal(&self, literal: LiteralValue) -> Result<LiteralValue, Error> {
-        Ok(literal)
+    fn visit_literal(&self, literal: LiteralValue) -> Result<Value, Error> {
+        Ok(literal.into())
+    }
+    fn visit_variable(&self, variable: Variable) -> Result<Value, Error> {
+        self.look_up_variable(&variable.name, variable.id)
+    }
+    fn visit_assign(&self, assign: Assign) -> Result<Value, Error> {
+        let value = self.evaluate(*assign.value)?;
+        match self.locals.borrow().get(&assign.id) {
+            Some(&distance) => {
+                Environment::assign_at(&self.environment(), distance, &assign.name, value.clone())?
+            }
+            None => self
+                .globals
+                .borrow_mut()
+                .assign(&assign.name, value.clone())?,
+        }
+        Ok(value)
+    }
+    fn visit_call(&self, call: Call) -> Result<Value, Error> {
+        let Call {
+            callee,
+            paren,
+            arguments,
+        } = call;
+        let callee = self.evaluate(*callee)?;
+
+        let arguments = arguments
+            .into_iter()
+            .map(|argument| self.evaluate(argument))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let function: &dyn Callable = match &callee {
+            Value::Function(function) => function.as_ref(),
+            Value::Class(class) => class,
+            Value::NativeFunction(native) => {
+                if arguments.len() != native.arity() {
+                    return Err(Error::tokened(
+                        "",
+                        paren,
+                        ErrorKind::ArityMismatch {
+                            expected: native.arity(),
+                            found: arguments.len(),
+                        },
+                    ));
+                }
+                return native.call(arguments).map_err(|message| {
+                    Error::tokened(message, paren, ErrorKind::NativeFunctionError)
+                });
+            }
+            _ => {
+                return Err(Error::tokened(
+                    "Can only call functions and classes.",
+                    paren,
+                    ErrorKind::NotCallable,
+                ))
+            }
+        };
+
+        if arguments.len() != function.arity() {
+            return Err(Error::tokened(
+                "",
+                paren,
+                ErrorKind::ArityMismatch {
+                    expected: function.arity(),
+                    found: arguments.len(),
+                },
+            ));
+        }
+
+        if self.call_depth.get() >= MAX_CALL_DEPTH {
+            return Err(Error::tokened("", paren, ErrorKind::StackOverflow));
+        }
+        self.call_depth.set(self.call_depth.get() + 1);
+        let result = function.call(self, arguments);
+        self.call_depth.set(self.call_depth.get() - 1);
+        result
+    }
+    fn visit_get(&self, get: Get) -> Result<Value, Error> {
+        match self.evaluate(*get.object)? {
+            Value::Instance(instance) => LoxInstance::get(&instance, &get.name),
+            _ => Err(Error::tokened(
+                "",
+                get.name,
+                ErrorKind::OnlyInstancesHaveProperties,
+            )),
+        }
+    }
+    fn visit_set(&self, set: Set) -> Result<Value, Error> {
+        match self.evaluate(*set.object)? {
+            Value::Instance(instance) => {
+                let value = self.evaluate(*set.value)?;
+                instance.borrow_mut().set(&set.name, value.clone());
+                Ok(value)
+            }
+            _ => Err(Error::tokened(
+                "",
+                set.name,
+                ErrorKind::OnlyInstancesHaveProperties,
+            )),
+        }
+    }
+    fn visit_this(&self, this: This) -> Result<Value, Error> {
+        self.look_up_variable(&this.keyword, this.id)
+    }
+    fn visit_super(&self, super_method: Super) -> Result<Value, Error> {
+        // unwrap: the resolver always binds `super`, it is only valid inside a subclass
+        let distance = *self.locals.borrow().get(&super_method.id).unwrap();
+        let environment = self.environment();
+        let superclass = match Environment::get_at(&environment, distance, &super_method.keyword)? {
+            Value::Class(class) => class,
+            _ => unreachable!("`super` is always bound to a class"),
+        };
+        // `this` is always bound in the scope just inside the one holding `super`
+        let instance = match Environment::ancestor(&environment, distance - 1)
+            .borrow()
+            .lookup("this")
+        {
+            Some(Value::Instance(instance)) => instance,
+            _ => unreachable!("`super` is only bound inside methods, where `this` is also bound"),
+        };
+
+        match superclass.find_method(super_method.method.name()) {
+            Some(method) => Ok(Value::Function(Rc::new(method.bind(instance)))),
+            None => Err(Error::tokened(
+                "",
+                super_method.method.clone(),
+                ErrorKind::UndefinedProperty(super_method.method.name().to_string()),
+            )),
+        }
+    }
+    fn visit_interpolation(&self, interpolation: Interpolation) -> Result<Value, Error> {
+        // every part is shown the same way `print` would show it
+        let mut string = String::new();
+        for part in interpolation.parts {
+            string.push_str(&self.evaluate(part)?.to_string());
+        }
+        Ok(Value::String(string))
+    }
+    fn visit_logical(&self, logical: Logical) -> Result<Value, Error> {
+        let left = self.evaluate(*logical.left)?;
+
+        // short-circuit, returning the operand that decided the outcome
+        match logical.operator.kind {
+            TokenKind::OR if left.is_truthy() => Ok(left),
+            TokenKind::AND if !left.is_truthy() => Ok(left),
+            TokenKind::OR | TokenKind::AND => self.evaluate(*logical.right),
+            _ => unreachable!(
+                "cannot evaluate this token here in a logical expression, bad input to evaluator"
+            ),
+        }
     }
 }
 
@@ -276,7 +633,19 @@ impl OwnedVisitor<Result<LiteralValue, Error>> for Evaluator {
 mod test {
     use super::{Error, Evaluator};
     use crate::ast::*;
-    use crate::lex::{Span, Token, TokenKind};
+    use crate::evaluate::{ErrorKind, Value};
+    use crate::lex::{Lexer, Span, Token, TokenKind};
+    use crate::resolve::Resolver;
+
+    /// Parse and resolve all of the source, ready to be executed
+    fn resolved(evaluator: &Evaluator, lexer: &Lexer) -> std::vec::IntoIter<Stmt> {
+        let statements = lexer
+            .advance_to_parsing()
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Error while parsing!");
+        evaluator.resolve(Resolver::resolve(&statements).expect("Error while resolving!"));
+        statements.into_iter()
+    }
 
     #[test]
     fn evaluation_works() -> Result<(), Error> {
@@ -288,6 +657,7 @@ mod test {
                     literal: None,
                     lexeme: "+".to_string(),
                     span: Span::new(),
+                    trivia: Vec::new(),
                 },
                 right: Box::new(Expr::Literal(LiteralValue::Number(2.0))),
             })),
@@ -296,6 +666,7 @@ mod test {
                 literal: None,
                 lexeme: "*".to_string(),
                 span: Span::new(),
+                trivia: Vec::new(),
             },
             right: Box::new(Expr::Binary(Binary {
                 left: Box::new(Expr::Literal(LiteralValue::Number(4.0))),
@@ -304,13 +675,14 @@ mod test {
                     literal: None,
                     lexeme: "-".to_string(),
                     span: Span::new(),
+                    trivia: Vec::new(),
                 },
                 right: Box::new(Expr::Literal(LiteralValue::Number(3.0))),
             })),
         });
 
-        let evaluator = Evaluator;
-        assert_eq!(LiteralValue::Number(3.), evaluator.evaluate(expression)?);
+        let evaluator = Evaluator::new();
+        assert_eq!(Value::Number(3.), evaluator.evaluate(expression)?);
         Ok(())
     }
 
@@ -324,6 +696,7 @@ mod test {
                     literal: None,
                     lexeme: "+".to_string(),
                     span: Span::new(),
+                    trivia: Vec::new(),
                 },
                 right: Box::new(Expr::Literal(LiteralValue::String(" World".into()))),
             })),
@@ -332,6 +705,7 @@ mod test {
                 literal: None,
                 lexeme: "+".to_string(),
                 span: Span::new(),
+                trivia: Vec::new(),
             },
             right: Box::new(Expr::Binary(Binary {
                 left: Box::new(Expr::Literal(LiteralValue::String("!".into()))),
@@ -340,16 +714,284 @@ mod test {
                     literal: None,
                     lexeme: "*".to_string(),
                     span: Span::new(),
+                    trivia: Vec::new(),
                 },
                 right: Box::new(Expr::Literal(LiteralValue::Number(3.0))),
             })),
         });
 
-        let evaluator = Evaluator;
+        let evaluator = Evaluator::new();
         assert_eq!(
-            LiteralValue::String("Hello World!!!".into()),
+            Value::String("Hello World!!!".into()),
             evaluator.evaluate(expression)?
         );
         Ok(())
     }
+
+    #[test]
+    fn variables_persist_between_statements() -> Result<(), Error> {
+        let evaluator = Evaluator::new();
+        let lexer = Lexer::new("var a = 1; var b; b = a + 2; a = b * 2;");
+        for statement in resolved(&evaluator, &lexer) {
+            evaluator.execute(statement)?;
+        }
+
+        let lexer = Lexer::new("a + b;");
+        let statement = resolved(&evaluator, &lexer).next().unwrap();
+        if let Stmt::ExprStmt(ExprStmt(expr)) = statement {
+            assert_eq!(Value::Number(9.), evaluator.evaluate(expr)?);
+        } else {
+            panic!("Expected source to parse as an expression statement")
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn undefined_variable_is_an_error() {
+        let evaluator = Evaluator::new();
+        let lexer = Lexer::new("print nope;");
+        let statement = resolved(&evaluator, &lexer).next().unwrap();
+
+        let error = evaluator.execute(statement).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::UndefinedVariable(name) if name == "nope"));
+    }
+
+    #[test]
+    fn blocks_shadow_and_drop_their_variables() -> Result<(), Error> {
+        let evaluator = Evaluator::new();
+        let lexer = Lexer::new(
+            r#"
+            var a = "outer";
+            var b = "untouched";
+            {
+                var a = "inner";
+                var temporary = a;
+                b = temporary;
+            }
+            "#,
+        );
+        for statement in resolved(&evaluator, &lexer) {
+            evaluator.execute(statement)?;
+        }
+
+        let lexer = Lexer::new("a + b; temporary;");
+        let mut statements = resolved(&evaluator, &lexer);
+        if let Some(Stmt::ExprStmt(ExprStmt(expr))) = statements.next() {
+            assert_eq!(
+                Value::String("outerinner".into()),
+                evaluator.evaluate(expr)?
+            );
+        } else {
+            panic!("Expected source to parse as an expression statement")
+        }
+        let leaked = evaluator.execute(statements.next().unwrap());
+        assert!(matches!(
+            leaked.unwrap_err().kind(),
+            ErrorKind::UndefinedVariable(name) if name == "temporary"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() -> Result<(), Error> {
+        let evaluator = Evaluator::new();
+        let lexer = Lexer::new(
+            r#"
+            var touched = false;
+            var a = nil or "default";
+            var b = false and (touched = true);
+            var c = "first" or (touched = true);
+            if (touched) a = "wrong"; else if (b == false) b = "else branch";
+            "#,
+        );
+        for statement in resolved(&evaluator, &lexer) {
+            evaluator.execute(statement)?;
+        }
+
+        let lexer = Lexer::new("a + b + c;");
+        if let Some(Stmt::ExprStmt(ExprStmt(expr))) = resolved(&evaluator, &lexer).next() {
+            assert_eq!(
+                Value::String("defaultelse branchfirst".into()),
+                evaluator.evaluate(expr)?
+            );
+        } else {
+            panic!("Expected source to parse as an expression statement")
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn loops_break_and_continue() -> Result<(), Error> {
+        let evaluator = Evaluator::new();
+        let lexer = Lexer::new(
+            r#"
+            var sum = 0;
+            for (var i = 0; i < 10; i = i + 1) {
+                if (i == 2) continue;
+                if (i == 5) break;
+                sum = sum + i;
+            }
+            var countdown = 3;
+            while (countdown > 0) countdown = countdown - 1;
+            "#,
+        );
+        for statement in resolved(&evaluator, &lexer) {
+            evaluator.execute(statement)?;
+        }
+
+        let lexer = Lexer::new("sum + countdown;");
+        if let Some(Stmt::ExprStmt(ExprStmt(expr))) = resolved(&evaluator, &lexer).next() {
+            // 0 + 1 + 3 + 4
+            assert_eq!(Value::Number(8.), evaluator.evaluate(expr)?);
+        } else {
+            panic!("Expected source to parse as an expression statement")
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn closures_capture_their_environment() -> Result<(), Error> {
+        let evaluator = Evaluator::new();
+        let lexer = Lexer::new(
+            r#"
+            fun makeCounter() {
+                var count = 0;
+                fun increment() {
+                    count = count + 1;
+                    return count;
+                }
+                return increment;
+            }
+            var counter = makeCounter();
+            counter();
+            counter();
+            fun fib(n) {
+                if (n < 2) return n;
+                return fib(n - 1) + fib(n - 2);
+            }
+            "#,
+        );
+        for statement in resolved(&evaluator, &lexer) {
+            evaluator.execute(statement)?;
+        }
+
+        let lexer = Lexer::new("counter() + fib(10);");
+        if let Some(Stmt::ExprStmt(ExprStmt(expr))) = resolved(&evaluator, &lexer).next() {
+            assert_eq!(Value::Number(58.), evaluator.evaluate(expr)?);
+        } else {
+            panic!("Expected source to parse as an expression statement")
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn bad_calls_are_errors() -> Result<(), Error> {
+        let evaluator = Evaluator::new();
+        let lexer = Lexer::new("fun pair(a, b) { return a; } pair(1); \"not a function\"();");
+        let mut statements = resolved(&evaluator, &lexer);
+        evaluator.execute(statements.next().unwrap())?;
+
+        let error = evaluator.execute(statements.next().unwrap()).unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::ArityMismatch {
+                expected: 2,
+                found: 1
+            }
+        ));
+        let error = evaluator.execute(statements.next().unwrap()).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::NotCallable));
+        Ok(())
+    }
+
+    #[test]
+    fn classes_with_inheritance() -> Result<(), Error> {
+        let evaluator = Evaluator::new();
+        let lexer = Lexer::new(
+            r#"
+            class Shape {
+                init(name) {
+                    this.name = name;
+                }
+                describe() {
+                    return this.name + " with area " + this.area();
+                }
+                area() {
+                    return 0;
+                }
+            }
+            class Square < Shape {
+                init(side) {
+                    super.init("square");
+                    this.side = side;
+                }
+                area() {
+                    return this.side * this.side;
+                }
+            }
+            var square = Square(3);
+            var method = square.describe;
+            "#,
+        );
+        for statement in resolved(&evaluator, &lexer) {
+            evaluator.execute(statement)?;
+        }
+
+        let lexer = Lexer::new("method();");
+        if let Some(Stmt::ExprStmt(ExprStmt(expr))) = resolved(&evaluator, &lexer).next() {
+            assert_eq!(
+                Value::String("square with area 9".into()),
+                evaluator.evaluate(expr)?
+            );
+        } else {
+            panic!("Expected source to parse as an expression statement")
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn bad_class_usage_is_an_error() {
+        let evaluator = Evaluator::new();
+        let lexer = Lexer::new(
+            "var NotAClass = 1; class Oops < NotAClass {} class Empty {} Empty().missing;",
+        );
+        let mut statements = resolved(&evaluator, &lexer);
+        evaluator.execute(statements.next().unwrap()).unwrap();
+
+        let error = evaluator.execute(statements.next().unwrap()).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::SuperclassMustBeClass));
+
+        evaluator.execute(statements.next().unwrap()).unwrap();
+        let error = evaluator.execute(statements.next().unwrap()).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::UndefinedProperty(name) if name == "missing"));
+    }
+
+    #[test]
+    fn native_values_can_be_passed_in_and_out() -> Result<(), Error> {
+        struct Config {
+            scale: f64,
+        }
+
+        let evaluator = Evaluator::new();
+        evaluator.define_global("config", Value::native(Config { scale: 2.0 }));
+        evaluator.define_global(
+            "scaled",
+            Value::native_function("scaled", 2, |arguments| match &arguments[..] {
+                [Value::Native(handle), Value::Number(n)] => handle
+                    .downcast_ref::<Config>()
+                    .map(|config| Value::Number(config.scale * n))
+                    .ok_or_else(|| "expected a config".to_string()),
+                _ => Err("expected a config and a number".to_string()),
+            }),
+        );
+
+        let lexer = Lexer::new("var result = scaled(config, 21); scaled(1, 2);");
+        let mut statements = resolved(&evaluator, &lexer);
+        evaluator.execute(statements.next().unwrap())?;
+        assert_eq!(Some(Value::Number(42.)), evaluator.get_global("result"));
+
+        let error = evaluator.execute(statements.next().unwrap()).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::NativeFunctionError));
+        Ok(())
+    }
 }
@@ -1,5 +1,15 @@
+mod callable;
+mod class;
+mod environment;
 mod error;
 mod evaluator;
+mod native;
+mod value;
 
+pub use callable::{Callable, LoxFunction};
+pub use class::{LoxClass, LoxInstance};
+pub use environment::Environment;
 pub use error::{Error, ErrorKind};
-pub use evaluator::Evaluator;
+pub use evaluator::{Evaluator, Flow};
+pub use native::{NativeFn, NativeFunction, NativeHandle};
+pub use value::Value;
@@ -0,0 +1,98 @@
+use std::any::Any;
+use std::rc::Rc;
+
+use crate::evaluate::Value;
+
+/// The signature of a function implemented in Rust and callable from Lox
+///
+/// Returning `Err` raises a runtime error at the call site with the given message.
+pub type NativeFn = dyn Fn(Vec<Value>) -> Result<Value, String>;
+
+/// A function implemented by the embedding application
+pub struct NativeFunction {
+    name: String,
+    arity: usize,
+    function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: impl Into<String>,
+        arity: usize,
+        function: impl Fn(Vec<Value>) -> Result<Value, String> + 'static,
+    ) -> Self {
+        NativeFunction {
+            name: name.into(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn call(&self, arguments: Vec<Value>) -> Result<Value, String> {
+        (self.function)(arguments)
+    }
+}
+
+impl std::fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish()
+    }
+}
+
+impl std::fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// Opaque data owned by the embedding application
+///
+/// Lox code can store and pass handles around but cannot look inside them,
+/// native functions can get the data back out with `NativeHandle::downcast_ref`.
+#[derive(Clone)]
+pub struct NativeHandle {
+    type_name: &'static str,
+    data: Rc<dyn Any>,
+}
+
+impl NativeHandle {
+    pub fn new<T: Any>(data: T) -> Self {
+        NativeHandle {
+            type_name: std::any::type_name::<T>(),
+            data: Rc::new(data),
+        }
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.data.downcast_ref()
+    }
+
+    pub fn ptr_eq(&self, other: &NativeHandle) -> bool {
+        Rc::ptr_eq(&self.data, &other.data)
+    }
+}
+
+impl std::fmt::Debug for NativeHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("NativeHandle")
+            .field("type_name", &self.type_name)
+            .finish()
+    }
+}
+
+impl std::fmt::Display for NativeHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "<native {}>", self.type_name)
+    }
+}
@@ -0,0 +1,204 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::ast::{format_number, LiteralValue};
+use crate::evaluate::{LoxClass, LoxFunction, LoxInstance, NativeFunction, NativeHandle};
+use crate::lex::escape_string;
+
+/// A value that exists while a Lox program is running
+///
+/// Unlike `LiteralValue` (which only describes what can be written in source code)
+/// runtime values can also be callables, classes and their instances, lists,
+/// and handles to data owned by an embedding application.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Number(f64),
+    String(String),
+    Bool(bool),
+    Nil,
+    Function(Rc<LoxFunction>),
+    NativeFunction(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Native(NativeHandle),
+}
+
+impl Value {
+    pub fn native_function(
+        name: impl Into<String>,
+        arity: usize,
+        function: impl Fn(Vec<Value>) -> Result<Value, String> + 'static,
+    ) -> Self {
+        Value::NativeFunction(Rc::new(NativeFunction::new(name, arity, function)))
+    }
+
+    pub fn list(values: Vec<Value>) -> Self {
+        Value::List(Rc::new(RefCell::new(values)))
+    }
+
+    pub fn native(data: impl std::any::Any) -> Self {
+        Value::Native(NativeHandle::new(data))
+    }
+
+    /// `false` and `nil` are falsey, everything else is truthy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Bool(false) | Value::Nil)
+    }
+
+    /// The name of this value's type, for use in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::Nil => "nil",
+            Value::Function(_) | Value::NativeFunction(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+            Value::List(_) => "list",
+            Value::Native(_) => "native",
+        }
+    }
+}
+
+impl From<LiteralValue> for Value {
+    fn from(literal: LiteralValue) -> Self {
+        match literal {
+            LiteralValue::Number(n) => Value::Number(n),
+            LiteralValue::String(s) => Value::String(s),
+            LiteralValue::Bool(b) => Value::Bool(b),
+            LiteralValue::Nil => Value::Nil,
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::Nil
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(option: Option<T>) -> Self {
+        option.map_or(Value::Nil, Into::into)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(values: Vec<T>) -> Self {
+        Value::list(values.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Primitive values compare by value, everything else compares by identity
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(l), Value::Number(r)) => l == r,
+            (Value::String(l), Value::String(r)) => l == r,
+            (Value::Bool(l), Value::Bool(r)) => l == r,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+            (Value::NativeFunction(l), Value::NativeFunction(r)) => Rc::ptr_eq(l, r),
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+            (Value::List(l), Value::List(r)) => Rc::ptr_eq(l, r),
+            (Value::Native(l), Value::Native(r)) => l.ptr_eq(r),
+            _ => false,
+        }
+    }
+}
+
+/// Formats values the way `print` shows them, strings are printed without quotes
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", format_number(*n)),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Bool(true) => write!(f, "true"),
+            Value::Bool(false) => write!(f, "false"),
+            Value::Nil => write!(f, "nil"),
+            Value::Function(function) => write!(f, "{}", function),
+            Value::NativeFunction(function) => write!(f, "{}", function),
+            Value::Class(class) => write!(f, "{}", class),
+            Value::Instance(instance) => write!(f, "{}", instance.borrow()),
+            Value::List(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match value {
+                        // quote strings inside lists so that `["a, b"]` and `["a", "b"]` differ
+                        Value::String(s) => write!(f, "{}", escape_string(s))?,
+                        value => write!(f, "{}", value)?,
+                    }
+                }
+                write!(f, "]")
+            }
+            Value::Native(handle) => write!(f, "{}", handle),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Value;
+
+    #[test]
+    fn values_display_like_print() {
+        assert_eq!("hello", Value::from("hello").to_string());
+        assert_eq!("nil", Value::from(None::<f64>).to_string());
+        assert_eq!(
+            r#"[1, "two", true]"#,
+            Value::list(vec![1.0.into(), "two".into(), true.into()]).to_string()
+        );
+    }
+
+    #[test]
+    fn numbers_display_exactly() {
+        assert_eq!("16777217", Value::from(16777217.0).to_string());
+        assert_eq!("-3", Value::from(-3.0).to_string());
+        assert_eq!("0.30000000000000004", Value::from(0.1 + 0.2).to_string());
+        assert_eq!("1e21", Value::from(1e21).to_string());
+        assert_eq!("1.5e-7", Value::from(1.5e-7).to_string());
+        for n in &[0.1, 1.0 / 3.0, 6.02e23, 123456.789, 2.5e-300] {
+            let printed = Value::from(*n).to_string();
+            assert_eq!(*n, printed.parse::<f64>().unwrap(), "{}", printed);
+        }
+    }
+
+    #[test]
+    fn objects_compare_by_identity() {
+        let list = Value::from(vec![1.0, 2.0]);
+        assert_eq!(list, list.clone());
+        assert_ne!(list, Value::from(vec![1.0, 2.0]));
+        assert_eq!(Value::from("a"), Value::from("a".to_string()));
+        assert_ne!(Value::Number(f64::NAN), Value::Number(f64::NAN));
+    }
+}
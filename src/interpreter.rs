@@ -1,43 +1,113 @@
-use std::{
-    fs::File,
-    io::{stdin, Read},
-};
+use std::io::stdin;
 
 use anyhow::Result;
+use peekaboo::IteratorPeekabooExt;
 
 use crate::{
     ast::{ExprStmt, Stmt},
-    evaluate::Evaluator,
-    lex::Lexer,
-    LoxError,
+    compile::Compiler,
+    diagnostics::{DiagnosticSink, HumanSink},
+    evaluate::{Evaluator, Value},
+    lex::{Lexer, SourceMap},
+    resolve::Resolver,
+    vm::Vm,
+    LoxError, Parser,
 };
 
+/// How deeply calls can nest before either backend reports a stack overflow
+pub const MAX_CALL_DEPTH: usize = 255;
+
+/// Which engine runs the program once it has been parsed and resolved
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// Walk the syntax tree directly with the `Evaluator`
+    TreeWalk,
+    /// Compile to bytecode and run it on the `Vm`
+    Bytecode,
+}
+
 pub struct Interpreter {
+    backend: Backend,
     evaluator: Evaluator,
+    vm: Vm,
+    /// In REPL mode the value of every expression statement is printed
+    repl_mode: bool,
+    /// Where errors are reported by `run_file` and `run_prompt`
+    sink: Box<dyn DiagnosticSink>,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        Interpreter::with_backend(Backend::TreeWalk)
+    }
+
+    pub fn with_backend(backend: Backend) -> Self {
         Interpreter {
-            evaluator: Evaluator,
+            backend,
+            evaluator: Evaluator::new(),
+            vm: Vm::new(),
+            repl_mode: false,
+            sink: Box::new(HumanSink::new(false)),
+        }
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Choose where errors are reported, they are rendered to stderr by default
+    pub fn set_sink(&mut self, sink: impl DiagnosticSink + 'static) {
+        self.sink = Box::new(sink);
+    }
+
+    /// Report an error, pointing into the source it came from
+    pub fn report(&mut self, source_map: &SourceMap, error: &LoxError) {
+        self.sink.emit_error(source_map, error);
+    }
+
+    /// Expose a value to Lox code as a global variable
+    pub fn define_global(&mut self, name: impl Into<String>, value: impl Into<Value>) {
+        let name = name.into();
+        let value = value.into();
+        self.vm.define_global(name.clone(), value.clone());
+        self.evaluator.define_global(name, value);
+    }
+
+    /// Read back a global variable after running some Lox code
+    ///
+    /// With the bytecode backend, functions, classes and instances defined in Lox can't be read back.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        match self.backend {
+            Backend::TreeWalk => self.evaluator.get_global(name),
+            Backend::Bytecode => self
+                .vm
+                .get_global(name)
+                .and_then(|value| value.to_evaluate()),
         }
     }
 
-    pub fn run_file(&mut self, path: String) -> Result<()> {
-        let mut file = match File::open(&path) {
-            Err(e) => panic!("couldn't open {}: {}", path, e),
-            Ok(file) => file,
+    /// Run a script, any errors (including not being able to read it) are reported to the sink
+    /// before being returned
+    pub fn run_file(&mut self, path: String) -> Result<(), LoxError> {
+        let src = match std::fs::read_to_string(&path) {
+            Ok(src) => src,
+            Err(e) => {
+                let error = LoxError::from(e);
+                self.report(&SourceMap::new(path, ""), &error);
+                return Err(error);
+            }
         };
 
-        let mut src = String::new();
-        match file.read_to_string(&mut src) {
-            Err(e) => panic!("couldn't read {}: {}", path, e),
-            Ok(_) => Ok(self.run(src)?),
+        let result = self.run(src.as_str());
+        if let Err(error) = &result {
+            self.report(&SourceMap::new(path, src), error);
         }
+        result
     }
 
     pub fn run_prompt(&mut self) -> Result<()> {
         let stdin = stdin();
+        self.repl_mode = true;
 
         loop {
             let mut line = String::new();
@@ -47,26 +117,441 @@ impl Interpreter {
                 break;
             };
             // otherwise we read a line of (possibly invalid) code and should try to run it
-            if let Err(e) = self.run(line.trim_end_matches('\n')) {
+            let line = line.trim_end_matches('\n');
+            if let Err(e) = self.run(line) {
                 // REPL is more forgiving of errors, print the error and keep looping!
-                eprintln!("{}", e);
+                self.report(&SourceMap::new("<repl>", line), &e);
             }
         }
 
+        self.repl_mode = false;
         Ok(())
     }
 
+    /// Parse and resolve the whole source, then execute every statement in order
+    ///
+    /// Nothing is executed if there are any syntax or resolver errors, all of them are returned together.
+    /// Lexer errors stop us before parsing, so that a bad token doesn't cause confusing syntax errors.
     pub fn run(&mut self, source: impl Into<String>) -> Result<(), LoxError> {
         let source = source.into();
 
         let scanner: Lexer = Lexer::new(source);
-        if let Some(statement) = scanner.advance_to_parsing().next() {
-            if let Stmt::ExprStmt(ExprStmt(expr)) = statement? {
-                let result = self.evaluator.evaluate(expr)?;
-                println!("{}", &result);
+        let (tokens, lexer_errors) = scanner.tokenize();
+        if !lexer_errors.is_empty() {
+            return Err(LoxError::LexerErrors(lexer_errors));
+        }
+
+        let (statements, errors) = Parser::new(tokens.into_iter().peekaboo()).parse_program();
+
+        if !errors.is_empty() {
+            return Err(LoxError::ParserErrors(errors));
+        }
+
+        let locals = Resolver::resolve(&statements).map_err(LoxError::ResolverErrors)?;
+
+        if self.backend == Backend::Bytecode {
+            let script = if self.repl_mode {
+                Compiler::compile_repl(&statements)?
+            } else {
+                Compiler::compile(&statements)?
+            };
+            self.vm.interpret(script)?;
+            return Ok(());
+        }
+
+        self.evaluator.resolve(locals);
+
+        for statement in statements {
+            match statement {
+                Stmt::ExprStmt(ExprStmt(expr)) if self.repl_mode => {
+                    let result = self.evaluator.evaluate(expr)?;
+                    println!("{}", &result);
+                }
+                statement => self.evaluator.execute(statement)?,
             }
         }
 
         Ok(())
     }
 }
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Backend, Interpreter, MAX_CALL_DEPTH};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::{diagnostics::CollectingSink, error::RuntimeError, evaluate::Value, LoxError};
+
+    /// Run the same program on both backends and check that they agree on the given globals
+    fn assert_backends_agree(source: &str, globals: &[&str]) -> Result<(), LoxError> {
+        let mut tree_walk = Interpreter::with_backend(Backend::TreeWalk);
+        let mut bytecode = Interpreter::with_backend(Backend::Bytecode);
+        tree_walk.run(source)?;
+        bytecode.run(source)?;
+
+        for name in globals {
+            let expected = tree_walk.get_global(name);
+            assert!(expected.is_some(), "`{}` is not defined", name);
+            assert_eq!(expected, bytecode.get_global(name), "`{}` differs", name);
+        }
+        Ok(())
+    }
+
+    /// The code of the runtime error the program stops with
+    fn runtime_error_code(backend: Backend, source: &str) -> &'static str {
+        match Interpreter::with_backend(backend).run(source) {
+            Err(LoxError::RuntimeError(RuntimeError::EvaluationError(error))) => {
+                error.kind().code()
+            }
+            Err(LoxError::RuntimeError(RuntimeError::VmError(error))) => error.kind().code(),
+            result => panic!(
+                "Expected a runtime error from `{}`, got {:?}",
+                source, result
+            ),
+        }
+    }
+
+    #[test]
+    fn missing_scripts_are_reported() {
+        let sink = Rc::new(RefCell::new(CollectingSink::new()));
+        let mut lox = Interpreter::new();
+        lox.set_sink(sink.clone());
+
+        let error = lox.run_file("does/not/exist.lox".to_string()).unwrap_err();
+        assert!(matches!(error, LoxError::IoError(_)));
+        let records = &sink.borrow().records;
+        assert_eq!(1, records.len());
+        assert_eq!("does/not/exist.lox", records[0].file);
+        assert!(records[0].message.starts_with("Couldn't read the script"));
+    }
+
+    #[test]
+    fn runs_every_statement() -> Result<(), LoxError> {
+        let mut lox = Interpreter::new();
+        lox.run("var a = 1; a = a + 1; fun double(n) { return n * 2; } var b = double(a);")?;
+
+        assert_eq!(Some(Value::Number(4.)), lox.get_global("b"));
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_to_parse_with_lexer_errors() {
+        let mut lox = Interpreter::new();
+        let result = lox.run("var a = 1; var b = \"\\q\"; var c = #;");
+
+        match result {
+            Err(LoxError::LexerErrors(errors)) => assert_eq!(2, errors.len()),
+            _ => panic!("Expected lexer errors"),
+        }
+        assert_eq!(None, lox.get_global("a"));
+    }
+
+    #[test]
+    fn refuses_to_run_with_syntax_errors() {
+        let mut lox = Interpreter::new();
+        let result = lox.run("var a = 1; print ; var b = 2; b = ;");
+
+        match result {
+            Err(LoxError::ParserErrors(errors)) => assert_eq!(2, errors.len()),
+            _ => panic!("Expected syntax errors"),
+        }
+        assert_eq!(None, lox.get_global("a"));
+    }
+
+    #[test]
+    fn closures_bind_to_the_scope_they_were_declared_in() -> Result<(), LoxError> {
+        let mut lox = Interpreter::new();
+        lox.run(
+            r#"
+            var a = "global";
+            var first;
+            var second;
+            {
+                fun show() {
+                    return a;
+                }
+                first = show();
+                var a = "block";
+                second = show();
+            }
+            "#,
+        )?;
+
+        assert_eq!(lox.get_global("first"), lox.get_global("second"));
+        Ok(())
+    }
+
+    #[test]
+    fn backends_agree_on_expressions() -> Result<(), LoxError> {
+        assert_backends_agree(
+            r#"
+            var a = 1 + 2 * 3 - 4 / 2;
+            var b = "con" + "cat" + 1;
+            var c = "ab" * 3;
+            var d = true + true;
+            var e = !nil == (1 < 2);
+            var f = nil or "default";
+            var g = false and undefined;
+            var h = 10 - 3 - 2;
+            var i = 100 / 10 / 5;
+            "#,
+            &["a", "b", "c", "d", "e", "f", "g", "h", "i"],
+        )?;
+
+        // operators of the same precedence group to the left
+        let mut lox = Interpreter::new();
+        lox.run("var h = 10 - 3 - 2; var i = 100 / 10 / 5; var j = 1 < 2 == true;")?;
+        assert_eq!(Some(Value::Number(5.)), lox.get_global("h"));
+        assert_eq!(Some(Value::Number(2.)), lox.get_global("i"));
+        assert_eq!(Some(Value::Bool(true)), lox.get_global("j"));
+        Ok(())
+    }
+
+    #[test]
+    fn backends_agree_on_interpolation() -> Result<(), LoxError> {
+        assert_backends_agree(
+            r#"
+            class Box {}
+            fun name() { return "lox"; }
+            var a = 1;
+            var b = 2.5;
+            var total = "total: ${a + b} items";
+            var only = "${a}";
+            var nested = "${"${name()}" + "!"} ${Box()} ${nil} ${a < b}";
+            var escaped = "\${a}";
+            "#,
+            &["total", "only", "nested", "escaped"],
+        )?;
+
+        let mut lox = Interpreter::new();
+        lox.run(r#"var s = "${1} and ${"two"}";"#)?;
+        assert_eq!(
+            Some(Value::String("1 and two".to_string())),
+            lox.get_global("s")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn backends_agree_on_unicode() -> Result<(), LoxError> {
+        assert_backends_agree(
+            r#"
+            var café = "\u{1F600}\x21";
+            var path = r"C:\lox\${café}" + café;
+            "#,
+            &["café", "path"],
+        )
+    }
+
+    #[test]
+    fn backends_report_the_same_runtime_errors() {
+        let sources = [
+            "print -nil;",
+            "print nil + nil;",
+            "print nil - 1;",
+            "print 2 * nil;",
+            "print nil / 2;",
+            "print \"ab\" * 1.5;",
+            "print nil < 1;",
+            "print undefined;",
+            "nil();",
+            "fun f(a) {} f();",
+            "class A {} print A().missing;",
+            "print 1.field;",
+            "var NotClass = 1; class B < NotClass {}",
+        ];
+        for source in sources.iter() {
+            assert_eq!(
+                runtime_error_code(Backend::TreeWalk, source),
+                runtime_error_code(Backend::Bytecode, source),
+                "`{}`",
+                source
+            );
+        }
+        assert_eq!(
+            "E005",
+            runtime_error_code(Backend::TreeWalk, "print 2 * nil;")
+        );
+    }
+
+    #[test]
+    fn runaway_recursion_is_an_error() {
+        // unoptimised tree-walking calls take far more stack than a test thread has,
+        // so run on a thread with as much stack as the main thread gets
+        let test = std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(|| {
+                let sources = [
+                    "fun f(n) { return f(n + 1); } f(0);",
+                    "class A { init() { A(); } } A();",
+                ];
+                for source in sources.iter() {
+                    assert_eq!("E016", runtime_error_code(Backend::TreeWalk, source));
+                    assert_eq!("E016", runtime_error_code(Backend::Bytecode, source));
+                }
+
+                // both backends allow exactly `MAX_CALL_DEPTH` calls, `count(n)` makes n + 1 of them
+                let count = "fun count(n) { if (n == 0) return 0; return 1 + count(n - 1); }";
+                let deepest = format!("{} var n = count({});", count, MAX_CALL_DEPTH - 1);
+                let too_deep = format!("{} count({});", count, MAX_CALL_DEPTH);
+                for backend in [Backend::TreeWalk, Backend::Bytecode].iter() {
+                    let mut lox = Interpreter::with_backend(*backend);
+                    lox.run(&deepest).unwrap();
+                    assert_eq!(
+                        Some(Value::Number((MAX_CALL_DEPTH - 1) as f64)),
+                        lox.get_global("n")
+                    );
+                    assert_eq!("E016", runtime_error_code(*backend, &too_deep));
+                }
+            })
+            .unwrap();
+        test.join().unwrap();
+    }
+
+    #[test]
+    fn numbers_keep_double_precision() -> Result<(), LoxError> {
+        let mut lox = Interpreter::new();
+        let source = r#"
+            var big = 16777216 + 1;
+            var money = "" + (0.1 + 0.2);
+            var huge = "" + 1e21;
+            var tiny = 1e-7 + "";
+            var same = huge == "${1e21}";
+            var infinite = "${1 / 0} ${-1 / 0} ${0 / 0}";
+        "#;
+        lox.run(source)?;
+
+        assert_eq!(Some(Value::Number(16777217.)), lox.get_global("big"));
+        assert_eq!(
+            Some(Value::String("0.30000000000000004".to_string())),
+            lox.get_global("money")
+        );
+        assert_eq!(
+            Some(Value::String("1e21".to_string())),
+            lox.get_global("huge")
+        );
+        assert_eq!(
+            Some(Value::String("1e-7".to_string())),
+            lox.get_global("tiny")
+        );
+        assert_eq!(Some(Value::Bool(true)), lox.get_global("same"));
+        assert_eq!(
+            Some(Value::String("inf -inf nan".to_string())),
+            lox.get_global("infinite")
+        );
+        assert_backends_agree(
+            source,
+            &["big", "money", "huge", "tiny", "same", "infinite"],
+        )
+    }
+
+    #[test]
+    fn backends_agree_on_control_flow() -> Result<(), LoxError> {
+        assert_backends_agree(
+            r#"
+            var evens = "";
+            var even = false;
+            for (var i = 0; i < 20; i = i + 1) {
+                even = !even;
+                var odd = !even;
+                if (i == 11) break;
+                if (odd) continue;
+                evens = evens + i + ",";
+            }
+            var n = 0;
+            while (true) {
+                { var inner = n; n = inner + 1; }
+                if (n >= 5) break;
+            }
+            "#,
+            &["evens", "n"],
+        )
+    }
+
+    #[test]
+    fn backends_agree_on_closures() -> Result<(), LoxError> {
+        assert_backends_agree(
+            r#"
+            fun makeCounter() {
+                var count = 0;
+                fun increment() {
+                    count = count + 1;
+                    return count;
+                }
+                return increment;
+            }
+            var counter = makeCounter();
+            counter();
+            var second = counter();
+
+            var captured = "";
+            for (var i = 0; i < 3; i = i + 1) {
+                fun show() { return i; }
+                captured = captured + show();
+            }
+
+            fun fib(n) {
+                if (n < 2) return n;
+                return fib(n - 1) + fib(n - 2);
+            }
+            var f = fib(15);
+            "#,
+            &["second", "captured", "f"],
+        )
+    }
+
+    #[test]
+    fn backends_agree_on_classes() -> Result<(), LoxError> {
+        assert_backends_agree(
+            r#"
+            class Shape {
+                init(name) {
+                    this.name = name;
+                }
+                describe() {
+                    return this.name + " with area " + this.area();
+                }
+                area() { return 0; }
+            }
+            class Square < Shape {
+                init(side) {
+                    super.init("square");
+                    this.side = side;
+                }
+                area() { return this.side * this.side; }
+                describe() { return "a " + super.describe(); }
+            }
+            var square = Square(3);
+            var description = square.describe();
+            var method = square.area;
+            var area = method();
+            var reinit = square.init(4).side;
+            "#,
+            &["description", "area", "reinit"],
+        )
+    }
+
+    #[test]
+    fn bytecode_backend_calls_native_functions() -> Result<(), LoxError> {
+        let mut lox = Interpreter::with_backend(Backend::Bytecode);
+        lox.define_global(
+            "double",
+            Value::native_function("double", 1, |arguments| match &arguments[0] {
+                Value::Number(n) => Ok(Value::Number(n * 2.)),
+                _ => Err("expected a number".to_string()),
+            }),
+        );
+        lox.run("var result = double(21);")?;
+
+        assert_eq!(Some(Value::Number(42.)), lox.get_global("result"));
+        assert!(lox.run("double(\"a\");").is_err());
+        Ok(())
+    }
+}
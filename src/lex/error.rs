@@ -18,6 +18,9 @@ pub enum Error {
 #[derive(Clone, Debug)]
 pub enum ErrorKind {
     UnterminatedString(String),
+    UnterminatedComment,
+    InvalidNumber,
+    UnterminatedInterpolation,
     InvalidEscape(char),
     InvalidSyntax,
     UnexpectedEOF,
@@ -50,12 +53,50 @@ impl Error {
             span,
         }
     }
+
+    pub fn kind(&self) -> &ErrorKind {
+        match self {
+            Error::SpannedError { kind, .. } => kind,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Error::SpannedError { message, .. } => message,
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        match self {
+            Error::SpannedError { span, .. } => span,
+        }
+    }
+}
+
+impl ErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::UnterminatedString(_) => "L001",
+            ErrorKind::InvalidEscape(_) => "L002",
+            ErrorKind::InvalidSyntax => "L003",
+            ErrorKind::UnexpectedEOF => "L004",
+            ErrorKind::UnterminatedComment => "L005",
+            ErrorKind::InvalidNumber => "L006",
+            ErrorKind::UnterminatedInterpolation => "L007",
+            ErrorKind::InternalInterpreterError => "L900",
+            ErrorKind::Fatal => "L901",
+            ErrorKind::Other => "L999",
+        }
+    }
 }
 
 impl std::fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             ErrorKind::UnterminatedString(s) => write!(f, "Unterminated String: `{}`.", s),
+            ErrorKind::UnterminatedComment => write!(f, "Unterminated Block Comment."),
+            ErrorKind::InvalidNumber => write!(f, "Invalid Number Literal."),
+            ErrorKind::UnterminatedInterpolation => write!(f, "Unterminated String Interpolation."),
             ErrorKind::InvalidEscape(c) => write!(f, "Invalid Escape character: `{}`.", c),
             ErrorKind::InvalidSyntax => write!(f, "Syntax Error."),
             ErrorKind::UnexpectedEOF => write!(f, "Unexpected End of Source Code."),
@@ -3,52 +3,94 @@
 mod error;
 pub use error::{Error, ErrorKind};
 
+mod source_map;
+pub use source_map::SourceMap;
+
 mod token;
-pub use token::{escape_string, Span, Token, TokenKind};
+pub use token::{escape_string, BorrowedToken, Span, Token, TokenKind, Trivia, TriviaKind};
 
 // parser.rs
 
+use std::collections::VecDeque;
 use std::str::Chars;
 
 // Peekaboo is for double peeking
 use peekaboo::{IteratorPeekabooExt, Peekaboo};
+use unicode_xid::UnicodeXID;
 
-use crate::ast::LiteralValue;
 use crate::parse::Parser;
+use crate::syntax::{self, SyntaxNode};
 
 pub type LexerResult<T> = Result<T, Error>;
 
 pub struct Lexer {
     source: String,
+    retain_comments: bool,
 }
 
 impl<'source> Lexer {
     pub fn new(source: impl Into<String>) -> Self {
         Lexer {
             source: source.into(),
+            retain_comments: false,
         }
     }
 
+    /// Keep regular comments as trivia on the token that follows them, doc comments are always kept
+    pub fn with_comments(mut self, retain_comments: bool) -> Self {
+        self.retain_comments = retain_comments;
+        self
+    }
+
     pub fn scan_tokens(&'source self) -> TokenStream<'source> {
         TokenStream {
+            tokens: self.scan_borrowed(),
+        }
+    }
+
+    /// Scan tokens that borrow their lexeme from the source instead of copying it
+    ///
+    /// Nothing is allocated per token (apart from kept comments), which matters for big scripts.
+    pub fn scan_borrowed(&'source self) -> BorrowedTokenStream<'source> {
+        BorrowedTokenStream {
+            text: &self.source,
             source: self.source.chars().peekaboo(),
             span: Span::new(),
+            pending: VecDeque::new(),
+            retain_comments: self.retain_comments,
+            trivia: Vec::new(),
+            interpolations: Vec::new(),
+            finished: false,
         }
     }
 
+    /// Lex the whole source, returning every valid token and every error found along the way
+    pub fn tokenize(&self) -> (Vec<Token>, Vec<Error>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        for result in self.scan_tokens() {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(error) => errors.push(error),
+            }
+        }
+        (tokens, errors)
+    }
+
+    /// Parse straight from the token stream, skipping over invalid tokens
+    ///
+    /// Lexer errors are not reported, use `tokenize` to find out about them first.
     pub fn advance_to_parsing(&'source self) -> Parser<impl Iterator<Item = Token> + 'source> {
-        let tokens = self
-            .scan_tokens()
-            .filter_map(|token_res| match token_res {
-                Ok(token) => Some(token),
-                Err(e) => {
-                    println!("{}", e);
-                    None
-                }
-            })
-            .peekaboo();
+        let tokens = self.scan_tokens().filter_map(Result::ok).peekaboo();
         Parser::new(tokens)
     }
+
+    /// The lossless syntax tree of the source, for tools that need every comment and space kept
+    ///
+    /// Errors end up in the tree too, see `syntax` for how.
+    pub fn syntax_tree(&self) -> SyntaxNode {
+        syntax::parse(&self.source)
+    }
 }
 
 /// TokenStream is a stream of tokens interpreted from individual characters of a source String
@@ -58,96 +100,147 @@ impl<'source> Lexer {
 /// The Iterator Item is `Result<Token, LoxError>` which allows the caller can decide how to
 /// handle individual invalid tokens. They may choose to error immediately or keep going
 /// and report errors once the TokenStream has ended.
+///
+/// Some errors don't stop a token from being made (like an invalid escape in a string),
+/// those are yielded just before the token they were found in.
+///
+/// The tokens own a copy of their lexeme and literal, see `BorrowedTokenStream` for tokens that don't.
 pub struct TokenStream<'source> {
+    tokens: BorrowedTokenStream<'source>,
+}
+
+/// The same stream as `TokenStream` but the tokens borrow from the source
+///
+/// This is where the scanning actually happens, `TokenStream` just makes owned copies.
+pub struct BorrowedTokenStream<'source> {
+    /// The whole source, tokens borrow their lexeme from it
+    text: &'source str,
     source: Peekaboo<Chars<'source>>,
     span: Span,
+    /// Items found while scanning the current token, waiting to be yielded
+    pending: VecDeque<LexerResult<BorrowedToken<'source>>>,
+    retain_comments: bool,
+    /// Comments waiting to be attached to the next token
+    trivia: Vec<Trivia>,
+    /// The `${` of every interpolation we're inside, and how many `{` inside it are still open
+    interpolations: Vec<(Span, usize)>,
+    /// Whether the EOF token has been made
+    finished: bool,
 }
 
-type MaybeTokenArgs = Option<LexerResult<(TokenKind, Option<LiteralValue>)>>;
+type MaybeTokenKind = Option<LexerResult<TokenKind>>;
 
-impl<'source> TokenStream<'source> {
+impl<'source> BorrowedTokenStream<'source> {
     /// This is the function
-    fn scan_token(&mut self) -> Option<LexerResult<Token>> {
-        if let Some(c) = self.advance() {
-            match self.match_char(c) {
-                Some(Ok((token_kind, literal_value))) => {
-                    Some(Ok(self.make_token(token_kind, literal_value)))
-                }
-                Some(Err(e)) => Some(Err(e)),
-                None => {
+    fn scan_token(&mut self) -> Option<LexerResult<BorrowedToken<'source>>> {
+        loop {
+            // We are at the beginning of the next lexeme.
+            self.span.reset();
+            if let Some(c) = self.advance() {
+                match self.match_char(c) {
+                    Some(Ok(token_kind)) => return Some(Ok(self.make_token(token_kind))),
+                    Some(Err(e)) => return Some(Err(e)),
                     // we consumed some lexemes but skipped making a token
                     // e.g. we just lexed a comment!
                     // we keep going to return a Token otherwise someone will think we ran out of tokens!
-                    self.next()
+                    None => {}
+                }
+            } else if !self.finished {
+                // no more characters to match, finish with an EOF token so the parser knows where the source ended
+                self.finished = true;
+                for (opening, _) in std::mem::take(&mut self.interpolations) {
+                    self.report_non_fatal_error(Error::spanned(
+                        "Expected a `}` to end this interpolation",
+                        opening,
+                        ErrorKind::UnterminatedInterpolation,
+                    ));
                 }
+                return Some(Ok(self.make_token(TokenKind::EOF)));
+            } else {
+                return None;
             }
-        } else {
-            // no more characters to match, TokenStream has finished
-            None
         }
     }
 
     /// Handles the logic of how to process the next token based on a single character
     /// It calls out to other more specific functions that handle the logic based on the first character match
-    fn match_char(&mut self, next_char: char) -> MaybeTokenArgs {
+    fn match_char(&mut self, next_char: char) -> MaybeTokenKind {
         match next_char {
             // ignore whitespace
-            ' ' | '\t' | '\r' => None,
-            // increment lines on every newline
-            '\n' => None,
+            // (the span keeps track of newlines)
+            ' ' | '\t' | '\r' | '\n' => None,
             '"' => self.string(),
+            'r' if self.source.peek_eq(&'"') => self.raw_string(),
             c if c.is_ascii_digit() => self.number(c),
-            c if (c.is_ascii_alphabetic() || c == '_') => self.keyword(c),
+            c if c == '_' || c.is_xid_start() => self.keyword(),
             c => self.operator(c),
         }
     }
 
-    fn operator(&mut self, c: char) -> MaybeTokenArgs {
+    fn operator(&mut self, c: char) -> MaybeTokenKind {
         let output = match c {
-            '(' => Some(Ok((TokenKind::LEFT_PAREN, None))),
-            ')' => Some(Ok((TokenKind::RIGHT_PAREN, None))),
-            '{' => Some(Ok((TokenKind::LEFT_BRACE, None))),
-            '}' => Some(Ok((TokenKind::RIGHT_BRACE, None))),
-            ',' => Some(Ok((TokenKind::COMMA, None))),
-            '.' => Some(Ok((TokenKind::DOT, None))),
-            '-' => Some(Ok((TokenKind::MINUS, None))),
-            '+' => Some(Ok((TokenKind::PLUS, None))),
-            ';' => Some(Ok((TokenKind::SEMICOLON, None))),
-            '*' => Some(Ok((TokenKind::STAR, None))),
+            '(' => Some(Ok(TokenKind::LEFT_PAREN)),
+            ')' => Some(Ok(TokenKind::RIGHT_PAREN)),
+            '{' => {
+                if let Some((_, open_braces)) = self.interpolations.last_mut() {
+                    *open_braces += 1;
+                }
+                Some(Ok(TokenKind::LEFT_BRACE))
+            }
+            '}' => match self.interpolations.last_mut() {
+                // the end of an interpolated expression, carry on with the rest of the string
+                Some((_, 0)) => {
+                    self.interpolations.pop();
+                    self.string()
+                }
+                Some((_, open_braces)) => {
+                    *open_braces -= 1;
+                    Some(Ok(TokenKind::RIGHT_BRACE))
+                }
+                None => Some(Ok(TokenKind::RIGHT_BRACE)),
+            },
+            ',' => Some(Ok(TokenKind::COMMA)),
+            '.' => Some(Ok(TokenKind::DOT)),
+            '-' => Some(Ok(TokenKind::MINUS)),
+            '+' => Some(Ok(TokenKind::PLUS)),
+            ';' => Some(Ok(TokenKind::SEMICOLON)),
+            '*' => Some(Ok(TokenKind::STAR)),
             '/' => {
                 if self.advance_if_eq('/').is_some() {
                     self.line_comment();
                     None
+                } else if self.advance_if_eq('*').is_some() {
+                    self.block_comment()
                 } else {
-                    Some(Ok((TokenKind::SLASH, None)))
+                    Some(Ok(TokenKind::SLASH))
                 }
             }
             '!' => {
                 if self.advance_if_eq('=').is_some() {
-                    Some(Ok((TokenKind::BANG_EQUAL, None)))
+                    Some(Ok(TokenKind::BANG_EQUAL))
                 } else {
-                    Some(Ok((TokenKind::BANG, None)))
+                    Some(Ok(TokenKind::BANG))
                 }
             }
             '=' => {
                 if self.advance_if_eq('=').is_some() {
-                    Some(Ok((TokenKind::EQUAL_EQUAL, None)))
+                    Some(Ok(TokenKind::EQUAL_EQUAL))
                 } else {
-                    Some(Ok((TokenKind::EQUAL, None)))
+                    Some(Ok(TokenKind::EQUAL))
                 }
             }
             '>' => {
                 if self.advance_if_eq('=').is_some() {
-                    Some(Ok((TokenKind::GREATER_EQUAL, None)))
+                    Some(Ok(TokenKind::GREATER_EQUAL))
                 } else {
-                    Some(Ok((TokenKind::GREATER, None)))
+                    Some(Ok(TokenKind::GREATER))
                 }
             }
             '<' => {
                 if self.advance_if_eq('=').is_some() {
-                    Some(Ok((TokenKind::LESS_EQUAL, None)))
+                    Some(Ok(TokenKind::LESS_EQUAL))
                 } else {
-                    Some(Ok((TokenKind::LESS, None)))
+                    Some(Ok(TokenKind::LESS))
                 }
             }
             c => Some(Err(Error::spanned(
@@ -163,7 +256,7 @@ impl<'source> TokenStream<'source> {
     fn advance(&mut self) -> Option<char> {
         match self.source.next() {
             Some(v) => {
-                self.span.advance();
+                self.span.advance(v);
                 Some(v)
             }
             None => None,
@@ -173,7 +266,7 @@ impl<'source> TokenStream<'source> {
     /// Thin wrapper around `Peekaboo::next_if_eq` which updates the span
     fn advance_if_eq(&mut self, expected: char) -> Option<char> {
         self.source.next_if_eq(&expected).and_then(|c| {
-            self.span.advance();
+            self.span.advance(c);
             Some(c)
         })
     }
@@ -181,135 +274,263 @@ impl<'source> TokenStream<'source> {
     /// Thin wrapper around `Peekaboo::next_if` which updates the span
     fn advance_if(&mut self, pred: impl FnOnce(&char) -> bool) -> Option<char> {
         self.source.next_if(pred).and_then(|c| {
-            self.span.advance();
+            self.span.advance(c);
             Some(c)
         })
     }
 
-    /// Create a token from a matched TokenKind and optional literal value.
-    fn make_token(&mut self, kind: TokenKind, literal: Option<LiteralValue>) -> Token {
-        Token {
+    /// Create a token from a matched TokenKind, its literal value is worked out later from the lexeme
+    fn make_token(&mut self, kind: TokenKind) -> BorrowedToken<'source> {
+        BorrowedToken {
             kind,
-            literal,
-            lexeme: kind.to_string(),
+            lexeme: &self.text[self.span.range()],
             span: self.span.clone(),
+            trivia: std::mem::take(&mut self.trivia),
         }
     }
 
-    /// Just a wrapper around eprintln! for now :)
+    /// Keep the comment that was just scanned, if we're keeping that kind of comment
+    fn add_trivia(&mut self, kind: TriviaKind) {
+        if kind == TriviaKind::DocComment || self.retain_comments {
+            self.trivia.push(Trivia {
+                kind,
+                text: self.text[self.span.range()].to_string(),
+                span: self.span.clone(),
+            });
+        }
+    }
+
+    /// Record an error that still lets us make the token being scanned
     fn report_non_fatal_error(&mut self, error: Error) {
-        eprintln!("{}", error);
+        self.pending.push_back(Err(error));
     }
 
-    /// Ignores the rest of the line
+    /// Skips the rest of the line, `///` (but not `////`) starts a doc comment
     fn line_comment(&mut self) {
-        while self.source.next_if(|&c| c != '\n').is_some() {}
+        let kind = if self.source.peek_eq(&'/') && !self.source.peek_ahead_check(|&c| c == '/') {
+            TriviaKind::DocComment
+        } else {
+            TriviaKind::LineComment
+        };
+        while self.advance_if(|&c| c != '\n').is_some() {}
+        self.add_trivia(kind);
     }
 
-    /// Processes a string token
-    fn string(&mut self) -> MaybeTokenArgs {
-        let mut string = String::new();
+    /// Skips a `/* */` comment, which can contain other block comments
+    ///
+    /// The opening `/*` has already been consumed, it's what gets pointed at if the comment is never closed.
+    fn block_comment(&mut self) -> MaybeTokenKind {
+        let opening = self.span.clone();
+        let mut depth = 1;
+        while depth > 0 {
+            match self.advance() {
+                Some('/') if self.advance_if_eq('*').is_some() => depth += 1,
+                Some('*') if self.advance_if_eq('/').is_some() => depth -= 1,
+                Some(_) => {}
+                None => {
+                    return Some(Err(Error::spanned(
+                        "Expected a closing `*/` for this comment",
+                        opening,
+                        ErrorKind::UnterminatedComment,
+                    )))
+                }
+            }
+        }
+        self.add_trivia(TriviaKind::BlockComment);
+        None
+    }
 
+    /// Processes a string token
+    ///
+    /// Escapes are only checked here, `BorrowedToken::literal` turns them into characters.
+    ///
+    /// A `${` in the string ends the token early as an `INTERPOLATION`, the tokens of the expression
+    /// come next and the `}` closing it carries on the string as another `INTERPOLATION` token.
+    fn string(&mut self) -> MaybeTokenKind {
         while let Some(c) = self.advance_if(|c| *c != '"') {
-            let next_string_char = match c {
-                '\n' => {
-                    self.span.newline();
-                    c
-                }
+            match c {
                 '\\' => {
-                    // escape sequence
-                    // check for all special characters
-                    match self.escape_character() {
-                        Ok(escape_character) => escape_character,
-                        Err(e) => {
-                            self.report_non_fatal_error(e);
-                            c
-                        }
+                    if let Err(e) = self.escape_character() {
+                        self.report_non_fatal_error(e);
                     }
                 }
-                c => c,
-            };
-            string.push(next_string_char);
+                '$' if self.advance_if_eq('{').is_some() => {
+                    let opening = Span {
+                        start: self.span.end - 2,
+                        start_line: self.span.end_line,
+                        start_character: self.span.end_character - 2,
+                        ..self.span.clone()
+                    };
+                    self.interpolations.push((opening, 0));
+                    return Some(Ok(TokenKind::INTERPOLATION));
+                }
+                _ => {}
+            }
         }
         if self.advance_if_eq('"').is_none() {
+            let string = token::unescape(&self.text[self.span.start() + 1..self.span.end()]);
             return Some(Err(Error::spanned(
                 r#"Expected closing `"`"#,
                 self.span.clone(),
                 ErrorKind::UnterminatedString(string),
             )));
         }
-        Some(Ok((TokenKind::STRING, Some(LiteralValue::String(string)))))
-    }
-
-    fn escape_character(&mut self) -> LexerResult<char> {
-        if self.advance_if_eq('n').is_some() {
-            Ok('\n')
-        } else if self.advance_if_eq('t').is_some() {
-            Ok('\t')
-        } else if self.advance_if_eq('r').is_some() {
-            Ok('\r')
-        } else if self.advance_if_eq('\\').is_some() {
-            Ok('\\')
-        } else if self.advance_if_eq('"').is_some() {
-            Ok('"')
+        if self.text[self.span.range()].starts_with('}') {
+            // the last part of an interpolated string
+            Some(Ok(TokenKind::INTERPOLATION))
         } else {
-            let next_char = self.source.peek().copied();
-            if let Some(c) = next_char {
-                Err(Error::spanned(
-                    r#"Expected one of `\n,\t,\r,\\`"#,
-                    self.span.clone(),
-                    ErrorKind::InvalidEscape(c),
-                ))
-            } else {
-                Err(Error::spanned(
+            Some(Ok(TokenKind::STRING))
+        }
+    }
+
+    /// Checks the escape after a `\` is valid and skips over it
+    fn escape_character(&mut self) -> LexerResult<()> {
+        let text = self.text;
+        let rest = &text[self.span.end()..];
+        let first = match rest.chars().next() {
+            Some(c) => c,
+            None => {
+                return Err(Error::spanned(
                     r#"while parsing an escape character"#,
                     self.span.clone(),
                     ErrorKind::UnexpectedEOF,
                 ))
             }
+        };
+
+        let (result, len) = match token::parse_escape(rest) {
+            Ok((_, len)) => (Ok(()), len),
+            Err((message, len)) => (Err(message), len),
+        };
+        // skip the escape, or as much of it as there was, so it's only reported once
+        for _ in rest[..len].chars() {
+            self.advance();
         }
+        result.map_err(|message| {
+            Error::spanned(message, self.span.clone(), ErrorKind::InvalidEscape(first))
+        })
+    }
+
+    /// Processes a raw string like `r"C:\path"`, nothing in it is escaped or interpolated
+    fn raw_string(&mut self) -> MaybeTokenKind {
+        // the opening quote
+        self.advance();
+        while self.advance_if(|c| *c != '"').is_some() {}
+        if self.advance_if_eq('"').is_none() {
+            return Some(Err(Error::spanned(
+                r#"Expected closing `"`"#,
+                self.span.clone(),
+                ErrorKind::UnterminatedString(
+                    self.text[self.span.start() + 2..self.span.end()].to_string(),
+                ),
+            )));
+        }
+        Some(Ok(TokenKind::STRING))
     }
 
     /// Processes a number token
-    fn number(&mut self, previous: char) -> MaybeTokenArgs {
-        let mut value = String::from(previous);
+    ///
+    /// As well as `12` and `3.5` there are `0x1F`, `0b1010`, exponents like `6.02e23`
+    /// and `_` separators between digits.
+    fn number(&mut self, first: char) -> MaybeTokenKind {
+        let number = if first == '0' && self.advance_if(|c| matches!(c, 'x' | 'X')).is_some() {
+            self.radix_number(16, "hexadecimal")
+        } else if first == '0' && self.advance_if(|c| matches!(c, 'b' | 'B')).is_some() {
+            self.radix_number(2, "binary")
+        } else {
+            self.decimal_number()
+        };
 
-        while let Some(n) = self.advance_if(|c| c.is_ascii_digit()) {
-            value.push(n);
+        match number {
+            Ok(()) => Some(Ok(TokenKind::NUMBER)),
+            Err(message) => {
+                // skip the rest of the malformed literal so it's only reported once
+                while self
+                    .advance_if(|c| c.is_ascii_alphanumeric() || *c == '_')
+                    .is_some()
+                {}
+                Some(Err(Error::spanned(
+                    message,
+                    self.span.clone(),
+                    ErrorKind::InvalidNumber,
+                )))
+            }
         }
+    }
 
-        // check if parsing a decimal
-        if self.source.peek_eq(&'.') && self.source.peek_ahead_check(|c| c.is_ascii_digit()) {
-            // add the decimal point
-            // unwrap: We just peeked at the decimal point
-            value.push(self.advance().unwrap());
-            // add the rest of the digits
-            while let Some(n) = self.advance_if(|c| c.is_ascii_digit()) {
-                value.push(n);
+    /// Consumes digits in `radix` and `_` separators, returning how many digits there were
+    ///
+    /// A separator has to be followed by another digit.
+    fn digits(&mut self, radix: u32) -> Result<usize, String> {
+        let mut count = 0;
+        loop {
+            if self.advance_if(|c| c.is_digit(radix)).is_some() {
+                count += 1;
+            } else if self.advance_if_eq('_').is_some() {
+                if !matches!(self.source.peek(), Some(c) if c.is_digit(radix)) {
+                    return Err("A digit separator `_` must be followed by a digit".to_string());
+                }
+            } else {
+                return Ok(count);
             }
         }
+    }
 
-        match value.parse() {
-            Ok(number) => Some(Ok((TokenKind::NUMBER, Some(LiteralValue::Number(number))))),
-            Err(_) => Some(Err(Error::internal_interpreter_error(
-                "while parsing a Literal Number",
-                self.span.clone(),
-            ))),
+    /// The digits of a `0x` or `0b` literal, the prefix has been consumed
+    fn radix_number(&mut self, radix: u32, name: &str) -> Result<(), String> {
+        // separators only go between digits, not between the prefix and the first digit
+        if self.source.peek_eq(&'_') {
+            return Err(format!(
+                "A digit separator `_` can't come straight after `{}`",
+                &self.text[self.span.range()]
+            ));
+        }
+        let count = self.digits(radix)?;
+
+        if let Some(c) = self.source.peek().filter(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("`{}` is not a {} digit", c, name));
+        }
+        if count == 0 {
+            return Err(format!(
+                "Expected {} digits after `{}`",
+                name,
+                &self.text[self.span.range()]
+            ));
         }
+        Ok(())
     }
 
-    /// Processes a keyword token
-    fn keyword(&mut self, previous: char) -> MaybeTokenArgs {
-        let mut value = String::from(previous);
+    /// The rest of a decimal number, the first digit has been consumed
+    fn decimal_number(&mut self) -> Result<(), String> {
+        self.digits(10)?;
 
-        while let Some(c) = self.advance_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
-            value.push(c);
+        // check if parsing a decimal
+        if self.source.peek_eq(&'.') && self.source.peek_ahead_check(|c| c.is_ascii_digit()) {
+            // skip the decimal point
+            self.advance();
+            self.digits(10)?;
         }
 
-        Some(Ok((
-            TokenKind::is_keyword(&value).unwrap_or(TokenKind::IDENTIFIER),
-            Some(LiteralValue::String(value)),
-        )))
+        if self.advance_if(|c| matches!(c, 'e' | 'E')).is_some() {
+            self.advance_if(|c| matches!(c, '+' | '-'));
+            if !matches!(self.source.peek(), Some(c) if c.is_ascii_digit()) {
+                return Err("Expected digits in the exponent".to_string());
+            }
+            self.digits(10)?;
+        }
+
+        Ok(())
+    }
+
+    /// Processes a keyword token, identifiers follow the Unicode XID rules (plus a leading `_`)
+    fn keyword(&mut self) -> MaybeTokenKind {
+        while self.advance_if(|c| c.is_xid_continue()).is_some() {}
+
+        // the name is the token's lexeme, there is no literal value
+        let name = &self.text[self.span.range()];
+        Some(Ok(
+            TokenKind::is_keyword(name).unwrap_or(TokenKind::IDENTIFIER)
+        ))
     }
 }
 
@@ -317,15 +538,35 @@ impl<'source> Iterator for TokenStream<'source> {
     type Item = LexerResult<Token>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        // We are at the beginning of the next lexeme.
-        self.span.reset();
-        self.scan_token()
+        self.tokens
+            .next()
+            .map(|result| result.map(BorrowedToken::into_owned))
+    }
+}
+
+impl<'source> Iterator for BorrowedTokenStream<'source> {
+    type Item = LexerResult<BorrowedToken<'source>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(pending) = self.pending.pop_front() {
+            return Some(pending);
+        }
+
+        let result = self.scan_token();
+        if self.pending.is_empty() {
+            result
+        } else {
+            // yield the non-fatal errors first, then the token they belong to
+            self.pending.extend(result);
+            self.pending.pop_front()
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Lexer, LexerResult};
+    use super::{Lexer, LexerResult, TokenKind, TriviaKind};
+    use crate::ast::LiteralValue;
 
     #[test]
     fn test() -> LexerResult<()> {
@@ -338,4 +579,362 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_tokens_keep_their_source_text() -> LexerResult<()> {
+        let source = "var _x1 = nil + 2.50 >= \"a\\tb\"; // done";
+
+        let lexer = Lexer::new(source);
+        let lexemes = lexer
+            .scan_tokens()
+            .map(|token| token.map(|token| token.lexeme))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(
+            vec![
+                "var",
+                "_x1",
+                "=",
+                "nil",
+                "+",
+                "2.50",
+                ">=",
+                "\"a\\tb\"",
+                ";",
+                // EOF
+                ""
+            ],
+            lexemes
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_tokenize_returns_every_error() {
+        let source = "var a = \"bad \\q escape\"; var b = @; var c = \"open";
+
+        let (tokens, errors) = Lexer::new(source).tokenize();
+
+        let kinds = errors.iter().map(|e| e.kind().code()).collect::<Vec<_>>();
+        assert_eq!(vec!["L002", "L003", "L001"], kinds);
+        // the string with a bad escape is still a token, the stray `@` is not
+        assert_eq!(13, tokens.len());
+    }
+
+    #[test]
+    fn test_borrowed_tokens_match_owned_tokens() -> LexerResult<()> {
+        let source = "var s = \"tab\\there\"; print 0x1F + 1_0.5e1 + s; /// doc\nfun f() {}";
+        let lexer = Lexer::new(source);
+
+        let owned = lexer.scan_tokens().collect::<Result<Vec<_>, _>>()?;
+        let borrowed = lexer.scan_borrowed().collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(owned.len(), borrowed.len());
+        for (owned, borrowed) in owned.iter().zip(&borrowed) {
+            assert_eq!(owned.kind, borrowed.kind);
+            assert_eq!(owned.lexeme, borrowed.lexeme);
+            assert_eq!(owned.literal, borrowed.literal());
+            assert_eq!(owned.span, borrowed.span);
+            assert_eq!(owned.trivia, borrowed.trivia);
+        }
+        assert_eq!(
+            Some(LiteralValue::String("tab\there".to_string())),
+            borrowed[3].literal()
+        );
+        assert_eq!(Some(LiteralValue::Number(105.0)), borrowed[8].literal());
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_stream_ends_with_eof() -> LexerResult<()> {
+        let source = "print 1;\n// trailing\n";
+
+        let tokens = Lexer::new(source)
+            .with_comments(true)
+            .scan_tokens()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let eof = tokens.last().unwrap();
+        assert_eq!(TokenKind::EOF, eof.kind);
+        assert_eq!(source.len()..source.len(), eof.span.range());
+        assert_eq!((3, 1), (eof.span.line(), eof.span.column()));
+        // comments after the last real token end up on EOF
+        assert_eq!("// trailing", eof.trivia[0].text);
+        assert_eq!(
+            1,
+            tokens.iter().filter(|t| t.kind == TokenKind::EOF).count()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_interpolated_strings_are_split() -> LexerResult<()> {
+        let source = r#""a ${ {"}"} } b ${"c ${d}"}""#;
+
+        let tokens = Lexer::new(source)
+            .scan_tokens()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let lexemes = tokens.iter().map(|t| t.lexeme.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            vec!["\"a ${", "{", "\"}\"", "}", "} b ${", "\"c ${", "d", "}\"", "}\"", ""],
+            lexemes
+        );
+        let literals = tokens
+            .iter()
+            .filter(|t| t.kind == TokenKind::INTERPOLATION)
+            .map(|t| t.literal.clone().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec!["\"a \"", "\" b \"", "\"c \"", "\"\"", "\"\""],
+            literals
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unclosed_interpolation() {
+        let source = "print \"sum ${a + b\";\nprint 1;";
+
+        let (_, errors) = Lexer::new(source).tokenize();
+
+        let error = errors
+            .iter()
+            .find(|e| e.kind().code() == "L007")
+            .expect("the `${` is never closed");
+        assert_eq!("${", &source[error.span().range()]);
+        assert_eq!((1, 12), (error.span().line(), error.span().column()));
+    }
+
+    #[test]
+    fn test_unicode_escapes_and_raw_strings() -> LexerResult<()> {
+        let source = r#""\u{1F600} \x41\u{e9}" r"C:\path\${x}" "\\" ré_sumé _ü2 变量"#;
+
+        let tokens = Lexer::new(source)
+            .scan_tokens()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let literals = tokens
+            .iter()
+            .take(3)
+            .map(|t| t.literal.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                Some(LiteralValue::String("😀 Aé".to_string())),
+                Some(LiteralValue::String(r"C:\path\${x}".to_string())),
+                Some(LiteralValue::String(r"\".to_string())),
+            ],
+            literals
+        );
+        let identifiers = tokens
+            .iter()
+            .filter(|t| t.kind == TokenKind::IDENTIFIER)
+            .map(|t| t.lexeme.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["ré_sumé", "_ü2", "变量"], identifiers);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        let source = r#""\u{110000}" "\u{12" "\u41" "\x4" "\u{1234567}" "\q" r"open"#;
+
+        let (tokens, errors) = Lexer::new(source).tokenize();
+
+        let messages = errors
+            .iter()
+            .map(|e| (e.kind().code(), &source[e.span().range()]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("L002", r#""\u{110000}"#),
+                ("L002", r#""\u{12"#),
+                ("L002", r#""\u"#),
+                ("L002", r#""\x4"#),
+                ("L002", r#""\u{1234567}"#),
+                ("L002", r#""\"#),
+                ("L001", r#"r"open"#),
+            ],
+            messages
+        );
+        // the strings themselves are still tokens, with their bad escapes left as written
+        assert_eq!(
+            Some(LiteralValue::String(r"\u{110000}".to_string())),
+            tokens[0].literal
+        );
+    }
+
+    #[test]
+    fn test_errors_come_just_before_their_token() {
+        let source = "// first\n/* second */ \"\\q\" a";
+
+        let results = Lexer::new(source)
+            .scan_tokens()
+            .map(|result| match result {
+                Ok(token) => Ok(token.kind),
+                Err(error) => Err(error.kind().code()),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                Err("L002"),
+                Ok(TokenKind::STRING),
+                Ok(TokenKind::IDENTIFIER),
+                Ok(TokenKind::EOF),
+            ],
+            results
+        );
+    }
+
+    #[test]
+    fn test_long_runs_of_comments() -> LexerResult<()> {
+        let source = format!(
+            "{}{}print 1;",
+            " \n".repeat(500_000),
+            "// c\n".repeat(100_000)
+        );
+
+        let lexer = Lexer::new(source);
+        let tokens = lexer.scan_borrowed().collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(4, tokens.len());
+        assert_eq!(600_001, tokens[0].span.line());
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_block_comments() -> LexerResult<()> {
+        let source = "a /* one /* two\n */ still ** comment */ b /**/ / c";
+
+        let tokens = Lexer::new(source)
+            .scan_tokens()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let lexemes = tokens.iter().map(|t| t.lexeme.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["a", "b", "/", "c", ""], lexemes);
+        assert_eq!(2, tokens[1].span.line());
+        Ok(())
+    }
+
+    #[test]
+    fn test_number_literals() -> LexerResult<()> {
+        let source = "0x1F 0XfF 0b1010 1_000_000 1e3 6.02E2 2.5e-1 0x1_0 12";
+
+        let numbers = Lexer::new(source)
+            .scan_tokens()
+            .filter(|token| !matches!(token, Ok(token) if token.kind == TokenKind::EOF))
+            .map(|token| {
+                token.map(|token| match token.literal {
+                    Some(LiteralValue::Number(n)) => n,
+                    literal => panic!("expected a number, got {:?}", literal),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(
+            vec![
+                31.0,
+                255.0,
+                10.0,
+                1_000_000.0,
+                1000.0,
+                602.0,
+                0.25,
+                16.0,
+                12.0
+            ],
+            numbers
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_number_literals() {
+        let cases = [
+            ("0x;", "Expected hexadecimal digits after `0x`", "0x"),
+            ("0b102;", "`2` is not a binary digit", "0b102"),
+            (
+                "0x_10;",
+                "A digit separator `_` can't come straight after `0x`",
+                "0x_10",
+            ),
+            (
+                "0B_1;",
+                "A digit separator `_` can't come straight after `0B`",
+                "0B_1",
+            ),
+            (
+                "1_;",
+                "A digit separator `_` must be followed by a digit",
+                "1_",
+            ),
+            (
+                "1__0;",
+                "A digit separator `_` must be followed by a digit",
+                "1__0",
+            ),
+            ("1e+;", "Expected digits in the exponent", "1e+"),
+        ];
+
+        for (source, message, literal) in cases.iter() {
+            let (tokens, errors) = Lexer::new(*source).tokenize();
+
+            assert_eq!(1, errors.len(), "{}", source);
+            assert_eq!("L006", errors[0].kind().code());
+            assert_eq!(*message, errors[0].message());
+            assert_eq!(*literal, &source[errors[0].span().range()]);
+            // only the semicolon and EOF are left
+            assert_eq!(2, tokens.len(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_comments_are_kept_as_trivia() -> LexerResult<()> {
+        let source = "// plain\n/// doc\n//// not doc\n/* block */ var a;";
+
+        let tokens = Lexer::new(source)
+            .scan_tokens()
+            .collect::<Result<Vec<_>, _>>()?;
+        let kinds = tokens[0].trivia.iter().map(|t| t.kind).collect::<Vec<_>>();
+        assert_eq!(vec![TriviaKind::DocComment], kinds);
+        assert_eq!(Some("doc".to_string()), tokens[0].doc_comment());
+
+        let tokens = Lexer::new(source)
+            .with_comments(true)
+            .scan_tokens()
+            .collect::<Result<Vec<_>, _>>()?;
+        let trivia = tokens[0]
+            .trivia
+            .iter()
+            .map(|t| (t.kind, t.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (TriviaKind::LineComment, "// plain"),
+                (TriviaKind::DocComment, "/// doc"),
+                (TriviaKind::LineComment, "//// not doc"),
+                (TriviaKind::BlockComment, "/* block */"),
+            ],
+            trivia
+        );
+        assert!(tokens[1].trivia.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let source = "print 1;\n  /* outer /* inner */\nprint 2;";
+
+        let (tokens, errors) = Lexer::new(source).tokenize();
+
+        // `print 1;` and EOF
+        assert_eq!(4, tokens.len());
+        assert_eq!(1, errors.len());
+        assert_eq!("L005", errors[0].kind().code());
+        // the error points at the opening `/*`
+        let span = errors[0].span();
+        assert_eq!((2, 3), (span.line(), span.column()));
+        assert_eq!("/*", &source[span.range()]);
+    }
 }
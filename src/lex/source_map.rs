@@ -0,0 +1,118 @@
+use super::Span;
+
+/// Maps byte offsets in a source file back to lines and columns
+///
+/// Used when reporting errors, to show the line of source code a `Span` points at.
+#[derive(Clone, Debug)]
+pub struct SourceMap {
+    name: String,
+    source: String,
+    /// byte offset of the start of every line
+    line_starts: Vec<usize>,
+}
+
+impl SourceMap {
+    pub fn new(name: impl Into<String>, source: impl Into<String>) -> Self {
+        let source = source.into();
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceMap {
+            name: name.into(),
+            source,
+            line_starts,
+        }
+    }
+
+    /// The file name, or something like `<repl>`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The 1-based line containing a byte offset
+    pub fn line(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line + 1,
+            Err(next_line) => next_line,
+        }
+    }
+
+    /// The 1-based line and column (in chars) of a byte offset
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let line = self.line(offset);
+        let line_start = self.line_starts[line - 1];
+        let offset = offset.min(self.source.len());
+        let column = self
+            .source
+            .get(line_start..offset)
+            .map_or(0, |text| text.chars().count());
+        (line, column + 1)
+    }
+
+    /// The text of a 1-based line, without its line ending
+    pub fn line_text(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.source.len(), |next| next - 1);
+        Some(self.source[start..end].trim_end_matches('\r'))
+    }
+
+    /// The source code a span covers
+    pub fn snippet(&self, span: &Span) -> Option<&str> {
+        self.source.get(span.range())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SourceMap;
+    use crate::Lexer;
+
+    #[test]
+    fn test_line_lookup() {
+        let map = SourceMap::new("test.lox", "var a;\r\nprint \"é\";\n\nprint a;");
+
+        assert_eq!(4, map.line_count());
+        assert_eq!((1, 1), map.line_column(0));
+        assert_eq!((2, 1), map.line_column(8));
+        // columns count characters, `é` is two bytes
+        assert_eq!((2, 10), map.line_column(18));
+        assert_eq!(Some("var a;"), map.line_text(1));
+        assert_eq!(Some(""), map.line_text(3));
+        assert_eq!(Some("print a;"), map.line_text(4));
+        assert_eq!(None, map.line_text(5));
+    }
+
+    #[test]
+    fn test_spans_agree_with_source_map() {
+        let source = "var a = \"multi\nline\";\n// comment\n  print a;";
+        let map = SourceMap::new("test.lox", source);
+        let lexer = Lexer::new(source);
+
+        for token in lexer.scan_tokens() {
+            let token = token.unwrap();
+            assert_eq!(
+                (token.span.line(), token.span.column()),
+                map.line_column(token.span.start())
+            );
+        }
+
+        let print = lexer
+            .scan_tokens()
+            .map(Result::unwrap)
+            .find(|token| token.lexeme == "print")
+            .unwrap();
+        assert_eq!((4, 3), (print.span.line(), print.span.column()));
+        assert_eq!(Some("print"), map.snippet(&print.span));
+    }
+}
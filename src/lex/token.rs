@@ -6,16 +6,215 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Option<LiteralValue>,
     pub span: Span,
+    /// Comments between the previous token and this one
+    ///
+    /// Doc comments are always kept, other comments only if the `Lexer` was asked to keep them.
+    pub trivia: Vec<Trivia>,
 }
 
-impl std::fmt::Display for LiteralValue {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            LiteralValue::Number(n) => write!(f, "{}", n),
-            LiteralValue::String(s) => write!(f, "{}", escape_string(s)),
-            LiteralValue::Bool(true) => write!(f, "true"),
-            LiteralValue::Bool(false) => write!(f, "false"),
-            LiteralValue::Nil => write!(f, "nil"),
+impl Token {
+    /// The name of an identifier (or keyword) token
+    pub fn name(&self) -> &str {
+        &self.lexeme
+    }
+
+    /// The text of the `///` comments just before this token, one line per comment
+    pub fn doc_comment(&self) -> Option<String> {
+        let lines = self
+            .trivia
+            .iter()
+            .filter_map(Trivia::doc_text)
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+}
+
+/// A `Token` that borrows its lexeme from the source instead of owning a copy
+///
+/// There's no stored literal either, `literal()` works it out from the lexeme when it's wanted.
+#[derive(Clone, Debug)]
+pub struct BorrowedToken<'src> {
+    pub kind: TokenKind,
+    pub lexeme: &'src str,
+    pub span: Span,
+    pub trivia: Vec<Trivia>,
+}
+
+impl<'src> BorrowedToken<'src> {
+    /// The value of a string or number token
+    pub fn literal(&self) -> Option<LiteralValue> {
+        match self.kind {
+            // the lexer only makes string tokens that have both quotes
+            TokenKind::STRING => Some(LiteralValue::String(match self.lexeme.strip_prefix('r') {
+                Some(raw) => raw[1..raw.len() - 1].to_string(),
+                None => unescape(&self.lexeme[1..self.lexeme.len() - 1]),
+            })),
+            // starts with `"` or `}` and ends with `${` or `"`
+            TokenKind::INTERPOLATION => {
+                let text = &self.lexeme[1..];
+                let text = text
+                    .strip_suffix("${")
+                    .or_else(|| text.strip_suffix('"'))
+                    .unwrap_or(text);
+                Some(LiteralValue::String(unescape(text)))
+            }
+            TokenKind::NUMBER => Some(LiteralValue::Number(number_value(self.lexeme))),
+            _ => None,
+        }
+    }
+
+    /// Copy everything the token borrows, for the parser
+    pub fn into_owned(self) -> Token {
+        Token {
+            kind: self.kind,
+            lexeme: self.lexeme.to_string(),
+            literal: self.literal(),
+            span: self.span,
+            trivia: self.trivia,
+        }
+    }
+}
+
+/// Reads the escape after a `\`, returning the character and how many bytes of `text` it took up
+///
+/// Escapes are `\n`, `\t`, `\r`, `\\`, `\"`, `\$`, `\x41` and `\u{1F600}`. On an error the
+/// byte count is how much of `text` still looked like part of the escape.
+pub(super) fn parse_escape(text: &str) -> Result<(char, usize), (String, usize)> {
+    let escaped = match text.chars().next() {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('\\') => '\\',
+        Some('"') => '"',
+        Some('$') => '$',
+        Some('x') => return byte_escape(text),
+        Some('u') => return unicode_escape(text),
+        _ => {
+            return Err((
+                r#"Expected one of `\n,\t,\r,\\,\",\$,\x,\u`"#.to_string(),
+                0,
+            ))
+        }
+    };
+    Ok((escaped, 1))
+}
+
+/// `\xNN`, two hex digits giving a character up to `\u{FF}`
+fn byte_escape(text: &str) -> Result<(char, usize), (String, usize)> {
+    let digits = text[1..]
+        .chars()
+        .take(2)
+        .take_while(char::is_ascii_hexdigit)
+        .count();
+    if digits < 2 {
+        return Err((
+            "`\\x` must be followed by two hex digits, like `\\x41`".to_string(),
+            1 + digits,
+        ));
+    }
+    // unwrap: two hex digits always make a valid char
+    let value = u32::from_str_radix(&text[1..3], 16).unwrap();
+    Ok((char::from_u32(value).unwrap(), 3))
+}
+
+/// `\u{...}`, one to six hex digits giving any Unicode scalar value
+fn unicode_escape(text: &str) -> Result<(char, usize), (String, usize)> {
+    if !text[1..].starts_with('{') {
+        return Err((
+            "`\\u` must be followed by a code point in braces, like `\\u{1F600}`".to_string(),
+            1,
+        ));
+    }
+    let digits = text[2..]
+        .chars()
+        .take_while(char::is_ascii_hexdigit)
+        .count();
+    let end = 2 + digits;
+    if !text[end..].starts_with('}') {
+        return Err(("Expected a `}` to end the unicode escape".to_string(), end));
+    }
+    if digits == 0 || digits > 6 {
+        return Err((
+            "A unicode escape has between 1 and 6 hex digits".to_string(),
+            end + 1,
+        ));
+    }
+    // unwrap: at most six hex digits fit in a u32
+    let value = u32::from_str_radix(&text[2..end], 16).unwrap();
+    match char::from_u32(value) {
+        Some(c) => Ok((c, end + 1)),
+        None => Err((
+            format!("`{:X}` is not a valid Unicode character", value),
+            end + 1,
+        )),
+    }
+}
+
+/// Replace the escapes in the text between a string's quotes, invalid escapes are left as they are
+pub(super) fn unescape(text: &str) -> String {
+    let mut string = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(backslash) = rest.find('\\') {
+        string.push_str(&rest[..backslash]);
+        rest = &rest[backslash + 1..];
+        match parse_escape(rest) {
+            Ok((escaped, len)) => {
+                string.push(escaped);
+                rest = &rest[len..];
+            }
+            Err(_) => string.push('\\'),
+        }
+    }
+    string.push_str(rest);
+    string
+}
+
+/// The value of a number lexeme the lexer has already checked
+pub(super) fn number_value(lexeme: &str) -> f64 {
+    let radix_value = |digits: &str, radix: u32| {
+        digits
+            .chars()
+            .filter_map(|c| c.to_digit(radix))
+            .fold(0.0, |n, digit| n * radix as f64 + digit as f64)
+    };
+    match lexeme.get(..2) {
+        Some("0x") | Some("0X") => radix_value(&lexeme[2..], 16),
+        Some("0b") | Some("0B") => radix_value(&lexeme[2..], 2),
+        _ if lexeme.contains('_') => lexeme.replace('_', "").parse().unwrap_or(f64::NAN),
+        _ => lexeme.parse().unwrap_or(f64::NAN),
+    }
+}
+
+/// Source text that isn't part of a token but is still worth keeping, i.e. comments
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    /// The comment exactly as written, including the `//` or `/* */`
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TriviaKind {
+    /// `/// like this`
+    DocComment,
+    LineComment,
+    BlockComment,
+}
+
+impl Trivia {
+    /// The contents of a doc comment without the leading `///` (and the space after it)
+    pub fn doc_text(&self) -> Option<&str> {
+        match self.kind {
+            TriviaKind::DocComment => {
+                let text = self.text.trim_start_matches("///");
+                Some(text.strip_prefix(' ').unwrap_or(text).trim_end())
+            }
+            _ => None,
         }
     }
 }
@@ -52,9 +251,12 @@ pub fn escape_string(s: &str) -> String {
 
 impl std::fmt::Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match &self.literal {
-            Some(l) => write!(f, "[{}] {}: {}", self.span, self.kind, l),
-            None => write!(f, "[{}] {}", self.span, self.kind),
+        match (&self.literal, self.kind) {
+            (Some(l), _) => write!(f, "[{}] {}: {}", self.span, self.kind, l),
+            (None, TokenKind::IDENTIFIER) => {
+                write!(f, "[{}] {}: {}", self.span, self.kind, self.lexeme)
+            }
+            (None, _) => write!(f, "[{}] {}", self.span, self.kind),
         }
     }
 }
@@ -89,10 +291,14 @@ pub enum TokenKind {
     IDENTIFIER,
     STRING,
     NUMBER,
+    /// Part of a string with `${expressions}` in it, see `TokenStream::string`
+    INTERPOLATION,
 
     // Keywords.
     AND,
+    BREAK,
     CLASS,
+    CONTINUE,
     ELSE,
     FALSE,
     FUN,
@@ -108,7 +314,7 @@ pub enum TokenKind {
     VAR,
     WHILE,
 
-    #[allow(dead_code)]
+    /// The last token, always there, its span is the very end of the source
     EOF,
 }
 
@@ -117,7 +323,9 @@ impl TokenKind {
     pub fn is_keyword(s: impl AsRef<str>) -> Option<Self> {
         match s.as_ref() {
             "and" => Some(TokenKind::AND),
+            "break" => Some(TokenKind::BREAK),
             "class" => Some(TokenKind::CLASS),
+            "continue" => Some(TokenKind::CONTINUE),
             "else" => Some(TokenKind::ELSE),
             "false" => Some(TokenKind::FALSE),
             "fun" => Some(TokenKind::FUN),
@@ -165,8 +373,11 @@ impl std::fmt::Display for TokenKind {
                 TokenKind::IDENTIFIER => "Ident",
                 TokenKind::STRING => "string",
                 TokenKind::NUMBER => "number",
+                TokenKind::INTERPOLATION => "interpolation",
                 TokenKind::AND => "and",
+                TokenKind::BREAK => "break",
                 TokenKind::CLASS => "class",
+                TokenKind::CONTINUE => "continue",
                 TokenKind::ELSE => "else",
                 TokenKind::FALSE => "false",
                 TokenKind::FUN => "fun",
@@ -187,8 +398,15 @@ impl std::fmt::Display for TokenKind {
     }
 }
 
-#[derive(Clone, Debug)]
+/// A region of the source code
+///
+/// `start` and `end` are byte offsets into the source, lines count from 1 and
+/// characters are the 0-based column (in chars, not bytes) within their line.
+/// Both ends are exclusive, as with ranges.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Span {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
     pub(crate) start_line: usize,
     pub(crate) end_line: usize,
     pub(crate) start_character: usize,
@@ -198,6 +416,8 @@ pub struct Span {
 impl Span {
     pub fn new() -> Self {
         Span {
+            start: 0,
+            end: 0,
             start_line: 1,
             start_character: 0,
             end_line: 1,
@@ -205,53 +425,89 @@ impl Span {
         }
     }
 
-    pub fn advance(&mut self) {
-        self.end_character += 1;
-    }
-
-    pub fn newline(&mut self) {
-        self.end_line += 1;
-        self.start_character = 0;
-        self.end_character = 0;
+    /// Extend the span over the next character of the source
+    pub fn advance(&mut self, c: char) {
+        self.end += c.len_utf8();
+        if c == '\n' {
+            self.end_line += 1;
+            self.end_character = 0;
+        } else {
+            self.end_character += 1;
+        }
     }
 
+    /// Start a new empty span where this one ends
     pub fn reset(&mut self) {
+        self.start = self.end;
         self.start_character = self.end_character;
         self.start_line = self.end_line;
     }
+
+    /// A span covering both `self` and `other`
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            start: self.start,
+            start_line: self.start_line,
+            start_character: self.start_character,
+            end: other.end,
+            end_line: other.end_line,
+            end_character: other.end_character,
+        }
+    }
+
+    /// Byte offset of the start of the span
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Byte offset just past the end of the span
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn line(&self) -> usize {
+        self.start_line
+    }
+
+    /// 1-based column of the first character, as shown to users
+    pub fn column(&self) -> usize {
+        self.start_character + 1
+    }
 }
 
+impl Default for Span {
+    fn default() -> Self {
+        Span::new()
+    }
+}
+
+/// Displays `line:column` positions, with 1-based inclusive columns
 impl std::fmt::Display for Span {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match (
-            self.end_line == self.start_line,
-            self.end_character == self.start_character + 1,
-        ) {
-            // single line, single char
-            (true, true) => write!(f, "{}:{}", self.start_line, self.start_character),
-            // single line, multiple chars
-            (true, false) => write!(
+        let last_character = self.end_character.max(self.start_character + 1);
+        if self.start_line != self.end_line {
+            write!(
                 f,
-                "{}:{}-{}",
+                "{}:{}-{}:{}",
                 self.start_line,
-                self.start_character,
-                self.end_character.checked_sub(1).unwrap_or(0),
-            ),
-            // multiple lines, single char
-            (false, true) => write!(
-                f,
-                "{}-{}:{}",
-                self.start_line, self.end_line, self.start_character
-            ),
-            // multiple lines, multiple chars
-            (false, false) => write!(
+                self.start_character + 1,
+                self.end_line,
+                self.end_character.max(1)
+            )
+        } else if last_character == self.start_character + 1 {
+            write!(f, "{}:{}", self.start_line, self.start_character + 1)
+        } else {
+            write!(
                 f,
-                "{}-{}:{}-{}",
+                "{}:{}-{}",
                 self.start_line,
-                self.end_line,
-                self.start_character,
-                self.end_character.checked_sub(1).unwrap_or(0)
-            ),
+                self.start_character + 1,
+                last_character
+            )
         }
     }
 }
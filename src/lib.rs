@@ -1,4 +1,6 @@
 pub mod ast;
+pub mod compile;
+pub mod diagnostics;
 pub mod evaluate;
 pub use ast::{
     printer,
@@ -10,10 +12,17 @@ mod error;
 pub use error::LoxError;
 
 pub mod interpreter;
-pub use interpreter::Interpreter;
+pub use interpreter::{Backend, Interpreter};
 
 pub mod lex;
 pub use lex::Lexer;
 
 mod parse;
 pub use parse::Parser;
+
+pub mod resolve;
+pub use resolve::Resolver;
+
+pub mod syntax;
+
+pub mod vm;
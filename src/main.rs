@@ -1,21 +1,71 @@
+use std::io::{IsTerminal, Read};
+
 use anyhow::Result;
 
-use lox::Interpreter;
+use lox::{
+    diagnostics::{HumanSink, JsonSink},
+    Backend, Interpreter, Lexer, LoxError,
+};
 
 fn show_usage() {
-    eprintln!("Usage: lox [script]");
+    eprintln!("Usage: lox [--vm] [--error-format=human|json] [--syntax-tree] [script]");
     std::process::exit(64);
 }
 
 fn main() -> Result<()> {
-    let mut args = std::env::args().skip(1);
-    let mut lox = Interpreter::new();
+    let mut args = std::env::args().skip(1).peekable();
+
+    let mut backend = Backend::TreeWalk;
+    let mut json = false;
+    let mut syntax_tree = false;
+    while let Some(option) = args.next_if(|arg| arg.starts_with("--")) {
+        match option.as_str() {
+            // run on the bytecode VM instead of walking the syntax tree
+            "--vm" => backend = Backend::Bytecode,
+            "--error-format=human" => json = false,
+            // one JSON object per diagnostic on stderr, for CI and editors
+            "--error-format=json" => json = true,
+            // print the lossless syntax tree of the script instead of running it
+            "--syntax-tree" => syntax_tree = true,
+            _ => show_usage(),
+        }
+    }
 
-    if let Some(path) = args.next() {
-        if args.count() > 0 {
-            show_usage();
+    let mut lox = Interpreter::with_backend(backend);
+    if json {
+        lox.set_sink(JsonSink::new(std::io::stderr()));
+    } else {
+        let colour = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        lox.set_sink(HumanSink::new(colour));
+    }
+
+    let path = args.next();
+    if args.count() > 0 {
+        show_usage();
+    };
+
+    if syntax_tree {
+        // without a script the source comes from stdin, e.g. `lox --syntax-tree < script.lox`
+        let source = match path {
+            Some(path) => std::fs::read_to_string(path)?,
+            None => {
+                let mut source = String::new();
+                std::io::stdin().read_to_string(&mut source)?;
+                source
+            }
         };
-        lox.run_file(path)?;
+        print!("{}", Lexer::new(source).syntax_tree().debug_tree());
+        return Ok(());
+    }
+
+    if let Some(path) = path {
+        // errors have already been reported, only the exit code is left to set
+        match lox.run_file(path) {
+            Ok(()) => {}
+            Err(LoxError::RuntimeError(_)) => std::process::exit(70),
+            Err(LoxError::IoError(_)) => std::process::exit(66),
+            Err(_) => std::process::exit(65),
+        }
     } else {
         lox.run_prompt()?;
     }
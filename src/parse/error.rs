@@ -9,7 +9,7 @@ pub enum Error {
     TokenedError {
         kind: ErrorKind,
         message: String,
-        token: Token,
+        token: Box<Token>,
     },
     #[error("{kind} {message}")]
     UntokenedError { kind: ErrorKind, message: String },
@@ -21,9 +21,20 @@ pub enum Error {
 pub enum ErrorKind {
     EOFWhileSynchronizing,
     InvalidExpression,
+    ExpectedOpeningParenthesis,
     UnclosedParentheses,
+    ExpectedOpeningBrace,
+    UnclosedBrace,
     UnexpectedEOF,
     ExprStmtMissingSemicolon,
+    MissingSemicolon,
+    ExpectedIdentifier,
+    ExpectedDot,
+    InvalidAssignmentTarget,
+    BreakOutsideLoop,
+    ContinueOutsideLoop,
+    TooManyArguments,
+    UnterminatedInterpolation,
     InternalInterpreterError,
     Fatal,
     Other,
@@ -40,7 +51,7 @@ impl Error {
     pub fn with_token(msg: impl Into<String>, kind: ErrorKind, token: Token) -> Self {
         Error::TokenedError {
             kind,
-            token,
+            token: Box::new(token),
             message: msg.into(),
         }
     }
@@ -48,7 +59,7 @@ impl Error {
     pub fn internal_interpreter_error(msg: impl Into<String>, token: Token) -> Self {
         Error::TokenedError {
             kind: ErrorKind::InternalInterpreterError,
-            token,
+            token: Box::new(token),
             message: msg.into(),
         }
     }
@@ -75,21 +86,73 @@ impl Error {
         }
     }
 
+    pub fn message(&self) -> &str {
+        match self {
+            Error::TokenedError { message, .. } | Error::UntokenedError { message, .. } => message,
+            Error::KindOnly(_) => "",
+        }
+    }
+
+    /// The token that caused this error, if there is one
+    pub fn token(&self) -> Option<&Token> {
+        match self {
+            Error::TokenedError { token, .. } => Some(token),
+            _ => None,
+        }
+    }
+
     pub fn is_fatal(&self) -> bool {
         self.kind() == ErrorKind::Fatal
     }
 }
 
+impl ErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::EOFWhileSynchronizing => "P001",
+            ErrorKind::InvalidExpression => "P002",
+            ErrorKind::ExpectedOpeningParenthesis => "P003",
+            ErrorKind::UnclosedParentheses => "P004",
+            ErrorKind::ExpectedOpeningBrace => "P005",
+            ErrorKind::UnclosedBrace => "P006",
+            ErrorKind::UnexpectedEOF => "P007",
+            ErrorKind::ExprStmtMissingSemicolon => "P008",
+            ErrorKind::MissingSemicolon => "P009",
+            ErrorKind::ExpectedIdentifier => "P010",
+            ErrorKind::ExpectedDot => "P011",
+            ErrorKind::InvalidAssignmentTarget => "P012",
+            ErrorKind::BreakOutsideLoop => "P013",
+            ErrorKind::ContinueOutsideLoop => "P014",
+            ErrorKind::TooManyArguments => "P015",
+            ErrorKind::UnterminatedInterpolation => "P016",
+            ErrorKind::InternalInterpreterError => "P900",
+            ErrorKind::Fatal => "P901",
+            ErrorKind::Other => "P999",
+        }
+    }
+}
+
 impl std::fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             ErrorKind::EOFWhileSynchronizing => write!(f, "Encountered errors while parsing."),
             ErrorKind::InvalidExpression => write!(f, "Invalid expression."),
+            ErrorKind::ExpectedOpeningParenthesis => write!(f, "Expected an Opening Parenthesis."),
             ErrorKind::UnclosedParentheses => write!(f, "Unclosed Parentheses."),
+            ErrorKind::ExpectedOpeningBrace => write!(f, "Expected an Opening Brace."),
+            ErrorKind::UnclosedBrace => write!(f, "Unclosed Brace."),
             ErrorKind::UnexpectedEOF => write!(f, "Unexpected End of Source Code."),
             ErrorKind::ExprStmtMissingSemicolon => {
                 write!(f, "Expression Statements must end with a Semicolon.")
             }
+            ErrorKind::MissingSemicolon => write!(f, "Statements must end with a Semicolon."),
+            ErrorKind::ExpectedIdentifier => write!(f, "Expected an Identifier."),
+            ErrorKind::ExpectedDot => write!(f, "Expected a Dot."),
+            ErrorKind::InvalidAssignmentTarget => write!(f, "Invalid Assignment Target."),
+            ErrorKind::BreakOutsideLoop => write!(f, "Break Outside of a Loop."),
+            ErrorKind::ContinueOutsideLoop => write!(f, "Continue Outside of a Loop."),
+            ErrorKind::TooManyArguments => write!(f, "Too Many Arguments."),
+            ErrorKind::UnterminatedInterpolation => write!(f, "Unterminated String Interpolation."),
             ErrorKind::InternalInterpreterError => write!(f, "Internal Interpreter Error."),
             ErrorKind::Fatal => write!(f, "Fatal Error!"),
             ErrorKind::Other => write!(f, "Unknown Error."),
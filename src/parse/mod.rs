@@ -5,6 +5,8 @@ pub use error::{Error, ErrorKind};
 
 // lexer.rs
 
+use std::collections::VecDeque;
+
 use peekaboo::Peekaboo;
 // for debugging, but too verbose so we comment out the #[trace] attributes most of the time
 #[allow(unused_imports)]
@@ -16,12 +18,19 @@ use crate::lex::{Token, TokenKind};
 
 trace::init_depth_var!();
 
+/// The most arguments a function can be declared with or called with
+pub const MAX_ARGUMENTS: usize = 255;
+
 pub struct Parser<I>
 where
     I: Iterator<Item = Token>,
 {
     tokens: Peekaboo<I>,
     current: usize,
+    /// how many loops enclose the statement being parsed, `break` and `continue` are only valid inside one
+    loop_depth: usize,
+    /// errors recovered from inside blocks, reported before the statement that contained them
+    recovered: VecDeque<Error>,
 }
 
 impl<I> Iterator for Parser<I>
@@ -31,12 +40,26 @@ where
     type Item = Result<Stmt, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.recovered.pop_front() {
+            return Some(Err(error));
+        }
         // check if we have finished receiving tokens
-        if self.tokens.peek().is_none() {
+        if self.at_end() {
             return None;
         }
         // else return the next Statement
-        Some(self.statement())
+        match self.declaration() {
+            Err(error) => {
+                // skip to the start of the next statement so that parsing can carry on
+                // and the caller can collect every error instead of only the first one
+                self.synchronize(error.token());
+                self.recovered.push_back(error);
+                self.recovered.pop_front().map(Err)
+            }
+            // the statement is missing whatever failed to parse inside it, so drop it
+            Ok(_) if !self.recovered.is_empty() => self.recovered.pop_front().map(Err),
+            Ok(statement) => Some(Ok(statement)),
+        }
     }
 }
 
@@ -45,91 +68,552 @@ where
     I: Iterator<Item = Token>,
 {
     pub fn new(tokens: Peekaboo<I>) -> Self {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            current: 0,
+            loop_depth: 0,
+            recovered: VecDeque::new(),
+        }
+    }
+
+    /// Parse the whole token stream, returning every statement and every error
+    ///
+    /// Parsing recovers at statement boundaries, so one run reports all the syntax errors in a file.
+    /// If there are any errors the statements are incomplete and shouldn't be run.
+    pub fn parse_program(self) -> (Vec<Stmt>, Vec<Error>) {
+        let mut statements = Vec::new();
+        let mut errors = Vec::new();
+        for result in self {
+            match result {
+                Ok(statement) => statements.push(statement),
+                Err(error) => errors.push(error),
+            }
+        }
+        (statements, errors)
+    }
+
+    pub fn declaration(&mut self) -> Result<Stmt, Error> {
+        // doc comments are attached to the keyword starting the declaration
+        let doc = self.tokens.peek().and_then(Token::doc_comment);
+        match self.tokens.peek().map(|t| t.kind) {
+            Some(TokenKind::VAR) => {
+                self.tokens.next();
+                self.var_declaration(doc)
+            }
+            Some(TokenKind::FUN) => {
+                self.tokens.next();
+                self.function("function", doc).map(Stmt::Function)
+            }
+            Some(TokenKind::CLASS) => {
+                self.tokens.next();
+                self.class_declaration(doc)
+            }
+            _ => self.statement(),
+        }
+    }
+
+    fn class_declaration(&mut self, doc: Option<String>) -> Result<Stmt, Error> {
+        let name = self.consume(
+            TokenKind::IDENTIFIER,
+            "Expect class name.",
+            ErrorKind::ExpectedIdentifier,
+        )?;
+
+        let superclass = if self
+            .tokens
+            .next_if(|ref t| t.kind == TokenKind::LESS)
+            .is_some()
+        {
+            let name = self.consume(
+                TokenKind::IDENTIFIER,
+                "Expect superclass name.",
+                ErrorKind::ExpectedIdentifier,
+            )?;
+            Some(Variable::new(name))
+        } else {
+            None
+        };
+
+        self.consume(
+            TokenKind::LEFT_BRACE,
+            "Expect '{' before class body.",
+            ErrorKind::ExpectedOpeningBrace,
+        )?;
+
+        let mut methods = Vec::new();
+        loop {
+            if self.at_end() {
+                return Err(
+                    self.end_of_input("Expect '}' after class body.", ErrorKind::UnclosedBrace)
+                );
+            }
+            match self.tokens.peek() {
+                Some(Token {
+                    kind: TokenKind::RIGHT_BRACE,
+                    ..
+                }) => {
+                    self.tokens.next();
+                    return Ok(Stmt::class(name, superclass, methods, doc));
+                }
+                token => {
+                    let doc = token.and_then(Token::doc_comment);
+                    methods.push(self.function("method", doc)?)
+                }
+            }
+        }
+    }
+
+    /// Parses a function's name, parameters and body, `kind` is used in error messages
+    fn function(&mut self, kind: &str, doc: Option<String>) -> Result<Function, Error> {
+        let name = self.consume(
+            TokenKind::IDENTIFIER,
+            &format!("Expect {} name.", kind),
+            ErrorKind::ExpectedIdentifier,
+        )?;
+        self.consume(
+            TokenKind::LEFT_PAREN,
+            &format!("Expect '(' after {} name.", kind),
+            ErrorKind::ExpectedOpeningParenthesis,
+        )?;
+
+        let mut params = Vec::new();
+        if !self.tokens.peek_check(|t| t.kind == TokenKind::RIGHT_PAREN) {
+            loop {
+                let param = self.consume(
+                    TokenKind::IDENTIFIER,
+                    "Expect parameter name.",
+                    ErrorKind::ExpectedIdentifier,
+                )?;
+                if params.len() >= MAX_ARGUMENTS {
+                    return Err(Error::with_token(
+                        format!("Can't have more than {} parameters.", MAX_ARGUMENTS),
+                        ErrorKind::TooManyArguments,
+                        param,
+                    ));
+                }
+                params.push(param);
+
+                if self
+                    .tokens
+                    .next_if(|ref t| t.kind == TokenKind::COMMA)
+                    .is_none()
+                {
+                    break;
+                }
+            }
+        }
+        self.consume(
+            TokenKind::RIGHT_PAREN,
+            "Expect ')' after parameters.",
+            ErrorKind::UnclosedParentheses,
+        )?;
+
+        self.consume(
+            TokenKind::LEFT_BRACE,
+            &format!("Expect '{{' before {} body.", kind),
+            ErrorKind::ExpectedOpeningBrace,
+        )?;
+
+        // loops outside of the function body can't be controlled from inside it
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let body = self.block_statements();
+        self.loop_depth = loop_depth;
+
+        Ok(Function {
+            name,
+            params,
+            body: body?,
+            doc,
+        })
+    }
+
+    fn var_declaration(&mut self, doc: Option<String>) -> Result<Stmt, Error> {
+        let name = self.consume(
+            TokenKind::IDENTIFIER,
+            "Expect variable name.",
+            ErrorKind::ExpectedIdentifier,
+        )?;
+
+        let initializer = if self
+            .tokens
+            .next_if(|ref t| t.kind == TokenKind::EQUAL)
+            .is_some()
+        {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume(
+            TokenKind::SEMICOLON,
+            "Expect ';' after variable declaration.",
+            ErrorKind::MissingSemicolon,
+        )?;
+        Ok(Stmt::var_declaration(name, initializer, doc))
     }
 
     pub fn statement(&mut self) -> Result<Stmt, Error> {
+        // the body of an if or a loop may be missing at the end of the source
+        if self.at_end() {
+            return Err(self.end_of_input("Expect statement.", ErrorKind::UnexpectedEOF));
+        }
+
         // we just checked that peek() is Some
         match self.tokens.peek().unwrap() {
             Token {
-                lexeme: _,
-                literal: _,
-                span: _,
                 kind: TokenKind::PRINT,
+                ..
             } => self.print_statement(),
+            Token {
+                kind: TokenKind::LEFT_BRACE,
+                ..
+            } => self.block(),
+            Token {
+                kind: TokenKind::IF,
+                ..
+            } => self.if_statement(),
+            Token {
+                kind: TokenKind::WHILE,
+                ..
+            } => self.while_statement(),
+            Token {
+                kind: TokenKind::FOR,
+                ..
+            } => self.for_statement(),
+            Token {
+                kind: TokenKind::BREAK | TokenKind::CONTINUE,
+                ..
+            } => self.loop_control_statement(),
+            Token {
+                kind: TokenKind::RETURN,
+                ..
+            } => self.return_statement(),
             _ => self.expression_statement(),
         }
     }
 
+    pub fn block(&mut self) -> Result<Stmt, Error> {
+        // consume the LEFT_BRACE token
+        self.tokens.next();
+
+        Ok(Stmt::block(self.block_statements()?))
+    }
+
+    /// Parses declarations until the closing brace of a block, the opening brace must already be consumed
+    fn block_statements(&mut self) -> Result<Vec<Stmt>, Error> {
+        let mut statements = Vec::new();
+        loop {
+            if self.at_end() {
+                return Err(self.end_of_input("Expect '}' after block.", ErrorKind::UnclosedBrace));
+            }
+            match self.tokens.peek() {
+                Some(Token {
+                    kind: TokenKind::RIGHT_BRACE,
+                    ..
+                }) => {
+                    self.tokens.next();
+                    return Ok(statements);
+                }
+                _ => match self.declaration() {
+                    Ok(statement) => statements.push(statement),
+                    Err(error) if error.is_fatal() => return Err(error),
+                    Err(error) => {
+                        // carry on with the rest of the block, the error is reported later
+                        self.synchronize(error.token());
+                        self.recovered.push_back(error);
+                    }
+                },
+            }
+        }
+    }
+
+    pub fn if_statement(&mut self) -> Result<Stmt, Error> {
+        // consume the IF token
+        self.tokens.next();
+
+        self.consume(
+            TokenKind::LEFT_PAREN,
+            "Expect '(' after 'if'.",
+            ErrorKind::ExpectedOpeningParenthesis,
+        )?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenKind::RIGHT_PAREN,
+            "Expect ')' after if condition.",
+            ErrorKind::UnclosedParentheses,
+        )?;
+
+        let then_branch = self.statement()?;
+        let else_branch = if self
+            .tokens
+            .next_if(|ref t| t.kind == TokenKind::ELSE)
+            .is_some()
+        {
+            Some(self.statement()?)
+        } else {
+            None
+        };
+
+        Ok(Stmt::if_statement(condition, then_branch, else_branch))
+    }
+
+    pub fn while_statement(&mut self) -> Result<Stmt, Error> {
+        // consume the WHILE token
+        self.tokens.next();
+
+        self.consume(
+            TokenKind::LEFT_PAREN,
+            "Expect '(' after 'while'.",
+            ErrorKind::ExpectedOpeningParenthesis,
+        )?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenKind::RIGHT_PAREN,
+            "Expect ')' after condition.",
+            ErrorKind::UnclosedParentheses,
+        )?;
+
+        let body = self.loop_body()?;
+        Ok(Stmt::while_statement(condition, body, None))
+    }
+
+    /// Parses a C-style for loop and desugars it into a while loop
+    pub fn for_statement(&mut self) -> Result<Stmt, Error> {
+        // consume the FOR token
+        self.tokens.next();
+
+        self.consume(
+            TokenKind::LEFT_PAREN,
+            "Expect '(' after 'for'.",
+            ErrorKind::ExpectedOpeningParenthesis,
+        )?;
+
+        let initializer = match self.tokens.peek().map(|t| t.kind) {
+            Some(TokenKind::SEMICOLON) => {
+                self.tokens.next();
+                None
+            }
+            Some(TokenKind::VAR) => {
+                self.tokens.next();
+                Some(self.var_declaration(None)?)
+            }
+            _ => Some(self.expression_statement()?),
+        };
+
+        let condition = match self.tokens.peek().map(|t| t.kind) {
+            Some(TokenKind::SEMICOLON) => Expr::literal_bool(true),
+            _ => self.expression()?,
+        };
+        self.consume(
+            TokenKind::SEMICOLON,
+            "Expect ';' after loop condition.",
+            ErrorKind::MissingSemicolon,
+        )?;
+
+        let increment = match self.tokens.peek().map(|t| t.kind) {
+            Some(TokenKind::RIGHT_PAREN) => None,
+            _ => Some(self.expression()?),
+        };
+        self.consume(
+            TokenKind::RIGHT_PAREN,
+            "Expect ')' after for clauses.",
+            ErrorKind::UnclosedParentheses,
+        )?;
+
+        let body = self.loop_body()?;
+        let while_loop = Stmt::while_statement(condition, body, increment);
+
+        match initializer {
+            Some(initializer) => Ok(Stmt::block(vec![initializer, while_loop])),
+            None => Ok(while_loop),
+        }
+    }
+
+    fn loop_body(&mut self) -> Result<Stmt, Error> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    /// Parses either a `break` or `continue` statement
+    pub fn loop_control_statement(&mut self) -> Result<Stmt, Error> {
+        // we just checked that peek() is Some
+        let keyword = self.tokens.next().unwrap();
+
+        if self.loop_depth == 0 {
+            return Err(match keyword.kind {
+                TokenKind::BREAK => Error::with_token(
+                    "Can't use 'break' outside of a loop.",
+                    ErrorKind::BreakOutsideLoop,
+                    keyword,
+                ),
+                _ => Error::with_token(
+                    "Can't use 'continue' outside of a loop.",
+                    ErrorKind::ContinueOutsideLoop,
+                    keyword,
+                ),
+            });
+        }
+
+        self.consume(
+            TokenKind::SEMICOLON,
+            "Expect ';' after loop control statement.",
+            ErrorKind::MissingSemicolon,
+        )?;
+
+        match keyword.kind {
+            TokenKind::BREAK => Ok(Stmt::break_statement(keyword)),
+            _ => Ok(Stmt::continue_statement(keyword)),
+        }
+    }
+
+    pub fn return_statement(&mut self) -> Result<Stmt, Error> {
+        // we just checked that peek() is Some
+        let keyword = self.tokens.next().unwrap();
+
+        let value = if self.tokens.peek_check(|t| t.kind == TokenKind::SEMICOLON) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+
+        self.consume(
+            TokenKind::SEMICOLON,
+            "Expect ';' after return value.",
+            ErrorKind::MissingSemicolon,
+        )?;
+        Ok(Stmt::return_statement(keyword, value))
+    }
+
     pub fn print_statement(&mut self) -> Result<Stmt, Error> {
         // consume the PRINT token
         self.tokens.next();
 
         let expr = self.expression()?;
+        self.consume(
+            TokenKind::SEMICOLON,
+            "Expect ';' after value.",
+            ErrorKind::MissingSemicolon,
+        )?;
         Ok(Stmt::print_statement(expr))
     }
 
     pub fn expression_statement(&mut self) -> Result<Stmt, Error> {
         let expr = self.expression()?;
-        if self
-            .tokens
-            .next_if(|ref t| t.kind == TokenKind::SEMICOLON)
-            .is_none()
-        {
-            if let Some(failed_token) = self.tokens.next() {
-                return Err(Error::with_token(
-                    "Expect ';' after expression.",
-                    ErrorKind::ExprStmtMissingSemicolon,
-                    failed_token,
-                ));
-            } else {
-                return Err(Error::without_token(
-                    "Expect ';' after expression, found EOF.",
-                    ErrorKind::ExprStmtMissingSemicolon,
-                ));
-            }
+        self.consume(
+            TokenKind::SEMICOLON,
+            "Expect ';' after expression.",
+            ErrorKind::ExprStmtMissingSemicolon,
+        )?;
+        Ok(Stmt::expression_statement(expr))
+    }
+
+    /// Consume the next token if it is of the expected kind, otherwise return an error
+    fn consume(
+        &mut self,
+        kind: TokenKind,
+        msg: &str,
+        error_kind: ErrorKind,
+    ) -> Result<Token, Error> {
+        if let Some(token) = self.tokens.next_if(|ref t| t.kind == kind) {
+            return Ok(token);
         }
+        Err(self.unexpected(msg, error_kind))
+    }
 
-        Ok(Stmt::expression_statement(expr))
+    /// An error pointing at the next token, which wasn't what we expected
+    fn unexpected(&mut self, msg: &str, error_kind: ErrorKind) -> Error {
+        if self.at_end() {
+            return self.end_of_input(msg, error_kind);
+        }
+        // leave the failed token alone, it might be the start of the next statement or the end of a block
+        // unwrap: we're not at the end so there is a token
+        let failed_token = self.tokens.peek().unwrap().clone();
+        Error::with_token(msg, error_kind, failed_token)
     }
 
-    pub fn expression_wrapper(&mut self) -> Result<Expr, Error> {
-        match self.expression() {
-            Err(err) => {
-                if !err.is_fatal() {
-                    // report error, synchronize and continue
-                    self.report(err);
-                    self.synchronize();
-                    if self.tokens.next().is_none() {
-                        // synchronize skipped all remaining tokens, no more errors to report.
-                        return Err(Error::KindOnly(ErrorKind::EOFWhileSynchronizing));
-                    }
-                } else {
-                    return Err(err);
-                }
-                // we can guarantee no infinite loop of errors as long as the token stream is finite
-                self.expression()
-            }
-            ok => ok,
+    /// Whether the next token is EOF (or there are no tokens at all)
+    fn at_end(&mut self) -> bool {
+        self.tokens
+            .peek()
+            .map_or(true, |token| token.kind == TokenKind::EOF)
+    }
+
+    /// An error for running out of tokens, pointing at the EOF token
+    ///
+    /// Token streams that don't end with an EOF token only get an error without a location.
+    fn end_of_input(&mut self, msg: &str, kind: ErrorKind) -> Error {
+        let msg = format!("Unexpected end of input. {}", msg);
+        match self.tokens.peek() {
+            Some(eof) => Error::with_token(msg, kind, eof.clone()),
+            None => Error::without_token(msg, kind),
         }
     }
 
     //#[trace]
     pub fn expression(&mut self) -> Result<Expr, Error> {
-        self.equality()
+        self.assignment()
+    }
+
+    //#[trace]
+    fn assignment(&mut self) -> Result<Expr, Error> {
+        let expr = self.logic_or()?;
+
+        if let Some(equals) = self.tokens.next_if(|ref t| t.kind == TokenKind::EQUAL) {
+            let value = self.assignment()?;
+
+            return match expr {
+                Expr::Variable(Variable { name, .. }) => Ok(Expr::assign(name, value)),
+                Expr::Get(Get { object, name }) => Ok(Expr::set(*object, name, value)),
+                _ => Err(Error::with_token(
+                    "Invalid assignment target.",
+                    ErrorKind::InvalidAssignmentTarget,
+                    equals,
+                )),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    //#[trace]
+    fn logic_or(&mut self) -> Result<Expr, Error> {
+        let mut left = self.logic_and()?;
+
+        while let Some(operator) = self.tokens.next_if(|ref t| t.kind == TokenKind::OR) {
+            self.current += 1;
+            let right = self.logic_and()?;
+            left = Expr::logical(left, operator, right);
+        }
+
+        Ok(left)
+    }
+
+    //#[trace]
+    fn logic_and(&mut self) -> Result<Expr, Error> {
+        let mut left = self.equality()?;
+
+        while let Some(operator) = self.tokens.next_if(|ref t| t.kind == TokenKind::AND) {
+            self.current += 1;
+            let right = self.equality()?;
+            left = Expr::logical(left, operator, right);
+        }
+
+        Ok(left)
     }
 
     //#[trace]
     fn equality(&mut self) -> Result<Expr, Error> {
-        let left = self.comparison()?;
+        let mut left = self.comparison()?;
 
         while let Some(operator) = self
             .tokens
             .next_if(|ref t| t.kind == TokenKind::EQUAL_EQUAL || t.kind == TokenKind::BANG_EQUAL)
         {
             self.current += 1;
-            let right = self.equality()?;
-            return Ok(Expr::binary(left, operator, right));
+            let right = self.comparison()?;
+            left = Expr::binary(left, operator, right);
         }
 
         Ok(left)
@@ -137,17 +621,17 @@ where
 
     //#[trace]
     fn comparison(&mut self) -> Result<Expr, Error> {
-        let left = self.terms()?;
+        let mut left = self.terms()?;
 
         while let Some(operator) = self.tokens.next_if(|ref t| {
-            t.kind == TokenKind::GREATER_EQUAL
-                || t.kind == TokenKind::LESS_EQUAL
+            t.kind == TokenKind::GREATER
                 || t.kind == TokenKind::GREATER_EQUAL
                 || t.kind == TokenKind::LESS
+                || t.kind == TokenKind::LESS_EQUAL
         }) {
             self.current += 1;
-            let right = self.comparison()?;
-            return Ok(Expr::binary(left, operator, right));
+            let right = self.terms()?;
+            left = Expr::binary(left, operator, right);
         }
 
         Ok(left)
@@ -155,15 +639,15 @@ where
 
     //#[trace]
     fn terms(&mut self) -> Result<Expr, Error> {
-        let left = self.factor()?;
+        let mut left = self.factor()?;
 
         while let Some(operator) = self
             .tokens
             .next_if(|ref t| t.kind == TokenKind::PLUS || t.kind == TokenKind::MINUS)
         {
             self.current += 1;
-            let right = self.terms()?;
-            return Ok(Expr::binary(left, operator, right));
+            let right = self.factor()?;
+            left = Expr::binary(left, operator, right);
         }
 
         Ok(left)
@@ -171,15 +655,15 @@ where
 
     //#[trace]
     fn factor(&mut self) -> Result<Expr, Error> {
-        let left = self.unary()?;
+        let mut left = self.unary()?;
 
         while let Some(operator) = self
             .tokens
             .next_if(|ref t| t.kind == TokenKind::STAR || t.kind == TokenKind::SLASH)
         {
             self.current += 1;
-            let right = self.factor()?;
-            return Ok(Expr::binary(left, operator, right));
+            let right = self.unary()?;
+            left = Expr::binary(left, operator, right);
         }
 
         Ok(left)
@@ -196,115 +680,231 @@ where
             return Ok(Expr::unary(operator, right));
         }
 
-        self.primary()
+        self.call()
+    }
+
+    //#[trace]
+    fn call(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.primary()?;
+
+        while let Some(token) = self
+            .tokens
+            .next_if(|ref t| t.kind == TokenKind::LEFT_PAREN || t.kind == TokenKind::DOT)
+        {
+            expr = match token.kind {
+                TokenKind::LEFT_PAREN => self.finish_call(expr)?,
+                _ => {
+                    let name = self.consume(
+                        TokenKind::IDENTIFIER,
+                        "Expect property name after '.'.",
+                        ErrorKind::ExpectedIdentifier,
+                    )?;
+                    Expr::get(expr, name)
+                }
+            };
+        }
+
+        Ok(expr)
+    }
+
+    /// Parses the arguments to a call, the opening parenthesis must already be consumed
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, Error> {
+        let mut arguments = Vec::new();
+        if !self.tokens.peek_check(|t| t.kind == TokenKind::RIGHT_PAREN) {
+            loop {
+                arguments.push(self.expression()?);
+                if self
+                    .tokens
+                    .next_if(|ref t| t.kind == TokenKind::COMMA)
+                    .is_none()
+                {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.consume(
+            TokenKind::RIGHT_PAREN,
+            "Expect ')' after arguments.",
+            ErrorKind::UnclosedParentheses,
+        )?;
+
+        if arguments.len() > MAX_ARGUMENTS {
+            return Err(Error::with_token(
+                format!("Can't have more than {} arguments.", MAX_ARGUMENTS),
+                ErrorKind::TooManyArguments,
+                paren,
+            ));
+        }
+
+        Ok(Expr::call(callee, paren, arguments))
     }
 
     //#[trace]
     fn primary(&mut self) -> Result<Expr, Error> {
-        match self.tokens.next() {
+        // never consume the EOF token, blocks still need to see it to report that they're unclosed
+        match self.tokens.next_if(|t| t.kind != TokenKind::EOF) {
             Some(Token {
-                lexeme: _,
-                span: _,
-                literal: _,
                 kind: TokenKind::TRUE,
+                ..
             }) => Ok(Expr::literal_bool(true)),
             Some(Token {
-                lexeme: _,
-                span: _,
-                literal: _,
                 kind: TokenKind::FALSE,
+                ..
             }) => Ok(Expr::literal_bool(false)),
             Some(Token {
-                lexeme: _,
-                span: _,
-                literal: _,
                 kind: TokenKind::NIL,
+                ..
             }) => Ok(Expr::literal_nil()),
             Some(Token {
-                lexeme: _,
-                span: _,
                 literal: Some(value),
                 kind: TokenKind::NUMBER | TokenKind::STRING,
+                ..
             }) => Ok(Expr::Literal(value)),
+            Some(
+                token @ Token {
+                    kind: TokenKind::IDENTIFIER,
+                    ..
+                },
+            ) => Ok(Expr::variable(token)),
+            Some(
+                token @ Token {
+                    kind: TokenKind::THIS,
+                    ..
+                },
+            ) => Ok(Expr::this(token)),
+            Some(
+                token @ Token {
+                    kind: TokenKind::SUPER,
+                    ..
+                },
+            ) => {
+                self.consume(
+                    TokenKind::DOT,
+                    "Expect '.' after 'super'.",
+                    ErrorKind::ExpectedDot,
+                )?;
+                let method = self.consume(
+                    TokenKind::IDENTIFIER,
+                    "Expect superclass method name.",
+                    ErrorKind::ExpectedIdentifier,
+                )?;
+                Ok(Expr::super_method(token, method))
+            }
+            Some(
+                token @ Token {
+                    kind: TokenKind::INTERPOLATION,
+                    ..
+                },
+            ) if token.lexeme.starts_with('"') => self.interpolation(token),
             Some(Token {
-                lexeme: _,
-                span: _,
-                literal: _,
                 kind: TokenKind::LEFT_PAREN,
+                ..
             }) => {
                 // match a grouping
                 let expr = self.expression()?;
-                match self
-                    .tokens
-                    .next_if(|ref t| t.kind == TokenKind::RIGHT_PAREN)
-                {
-                    None => {
-                        if let Some(failed_token) = self.tokens.next() {
-                            Err(Error::with_token(
-                                "Expected a closing parenthesis",
-                                ErrorKind::UnclosedParentheses,
-                                failed_token,
-                            ))
-                        } else {
-                            Err(Error::without_token(
-                                "While a parenthesis was open",
-                                ErrorKind::UnexpectedEOF,
-                            ))
-                        }
-                    }
-                    Some(_) => Ok(Expr::grouping(expr)),
-                }
+                self.consume(
+                    TokenKind::RIGHT_PAREN,
+                    "Expected a closing parenthesis",
+                    ErrorKind::UnclosedParentheses,
+                )?;
+                Ok(Expr::grouping(expr))
             }
             Some(unexpected_token) => Err(Error::with_token(
                 "Expected a literal value, or an opening parenthesis",
                 ErrorKind::InvalidExpression,
                 unexpected_token,
             )),
-            None => Err(Error::without_token(
-                "while parsing an expression",
-                ErrorKind::UnexpectedEOF,
-            )),
+            None => Err(self.end_of_input("Expect expression.", ErrorKind::UnexpectedEOF)),
         }
     }
 
-    fn synchronize(&mut self) {
-        while let Some(token) = self.tokens.next() {
-            match token.kind {
-                TokenKind::SEMICOLON => return,
-                _ => {
-                    if let Some(ref next_token) = self.tokens.peek() {
-                        match next_token.kind {
-                            TokenKind::CLASS
-                            | TokenKind::FUN
-                            | TokenKind::VAR
-                            | TokenKind::FOR
-                            | TokenKind::IF
-                            | TokenKind::WHILE
-                            | TokenKind::PRINT
-                            | TokenKind::RETURN => return,
-                            _ => {}
-                        }
-                    }
+    /// Parses the rest of an interpolated string, `first` is the part up to the first `${`
+    ///
+    /// The lexer makes `INTERPOLATION` tokens for every piece of string text, the ones after
+    /// the first start with the `}` ending the expression before them.
+    fn interpolation(&mut self, first: Token) -> Result<Expr, Error> {
+        let mut parts = Vec::new();
+        let mut segment = first;
+        loop {
+            match segment.literal {
+                Some(LiteralValue::String(ref text)) if text.is_empty() => {}
+                Some(value) => parts.push(Expr::Literal(value)),
+                None => {}
+            }
+            if !segment.lexeme.ends_with("${") {
+                return Ok(Expr::interpolation(parts));
+            }
+
+            parts.push(self.expression()?);
+
+            segment = match self
+                .tokens
+                .next_if(|t| t.kind == TokenKind::INTERPOLATION && t.lexeme.starts_with('}'))
+            {
+                Some(segment) => segment,
+                None => {
+                    return Err(self.unexpected(
+                        "Expect '}' after the interpolated expression.",
+                        ErrorKind::UnterminatedInterpolation,
+                    ))
                 }
             }
         }
     }
 
-    fn report(&mut self, err: Error) -> () {
-        eprintln!("{}", err);
+    /// Discard tokens until we are (probably) at the start of the next statement
+    ///
+    /// `failed_token` is the token that caused the error, it may or may not have been consumed.
+    /// Stops before a `}` so that an enclosing block can still be closed.
+    fn synchronize(&mut self, failed_token: Option<&Token>) {
+        if let Some(failed_token) = failed_token {
+            let consumed = self
+                .tokens
+                .peek()
+                .map_or(true, |next| next.span != failed_token.span);
+            if consumed && failed_token.kind == TokenKind::SEMICOLON {
+                return;
+            }
+        }
+
+        while let Some(next_token) = self.tokens.peek() {
+            match next_token.kind {
+                TokenKind::CLASS
+                | TokenKind::FUN
+                | TokenKind::VAR
+                | TokenKind::FOR
+                | TokenKind::IF
+                | TokenKind::WHILE
+                | TokenKind::PRINT
+                | TokenKind::RETURN
+                | TokenKind::BREAK
+                | TokenKind::CONTINUE
+                | TokenKind::RIGHT_BRACE
+                | TokenKind::EOF => return,
+                TokenKind::SEMICOLON => {
+                    self.tokens.next();
+                    return;
+                }
+                _ => {
+                    self.tokens.next();
+                }
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
+    use super::ErrorKind;
     use crate::ast::{ExprStmt, Stmt};
-    use crate::lex::Lexer;
+    use crate::lex::{Lexer, TokenKind};
     use crate::printer::DebugPrinter;
 
     #[test]
     fn test_parser() {
         let source = r#"
-            2 + (3 - 4) * 9 != "foo"
+            2 + (3 - 4) * 9 != "foo";
         "#;
 
         let scanner = Lexer::new(source);
@@ -324,4 +924,137 @@ mod test {
             panic!("Expected source to parse as an expression statement")
         }
     }
+
+    #[test]
+    fn test_interpolation() {
+        let scanner = Lexer::new(r#""total: ${a + b} items, ${"nested ${c}"}";"#);
+
+        let statement = scanner.advance_to_parsing().next().unwrap().unwrap();
+
+        if let Stmt::ExprStmt(ExprStmt(e)) = statement {
+            assert_eq!(
+                r#"(interpolate "total: " (+ a b) " items, " (interpolate "nested " c))"#,
+                DebugPrinter::print(&e)
+            );
+        } else {
+            panic!("Expected source to parse as an expression statement")
+        }
+    }
+
+    #[test]
+    fn test_break_outside_loop() {
+        let scanner = Lexer::new("if (true) break;");
+
+        let error = scanner
+            .advance_to_parsing()
+            .next()
+            .unwrap()
+            .expect_err("break outside of a loop should not parse");
+
+        assert_eq!(ErrorKind::BreakOutsideLoop, error.kind());
+    }
+
+    #[test]
+    fn test_doc_comments_are_attached_to_declarations() {
+        let source = r#"
+            /// Makes points
+            ///
+            /// Has an x and a y.
+            class Point {
+                /// The distance from the origin
+                length() { return 0; }
+                other() {}
+            }
+            // not a doc comment
+            var origin;
+            /// Adds one
+            fun inc(x) { return x + 1; }
+        "#;
+
+        let scanner = Lexer::new(source);
+        let (statements, errors) = scanner.advance_to_parsing().parse_program();
+        assert!(errors.is_empty());
+
+        match &statements[..] {
+            [Stmt::Class(class), Stmt::Var(var), Stmt::Function(function)] => {
+                assert_eq!(
+                    Some("Makes points\n\nHas an x and a y."),
+                    class.doc.as_deref()
+                );
+                assert_eq!(
+                    Some("The distance from the origin"),
+                    class.methods[0].doc.as_deref()
+                );
+                assert_eq!(None, class.methods[1].doc);
+                assert_eq!(None, var.doc);
+                assert_eq!(Some("Adds one"), function.doc.as_deref());
+            }
+            statements => panic!("unexpected statements: {:?}", statements),
+        }
+    }
+
+    #[test]
+    fn test_unexpected_end_of_input_points_at_eof() {
+        let source = "var a = 1;\nwhile (a < 2) {\n  print (a";
+
+        let scanner = Lexer::new(source);
+        let (_, errors) = scanner.advance_to_parsing().parse_program();
+
+        assert_eq!(2, errors.len());
+        for error in &errors {
+            let token = error.token().expect("the error should point at EOF");
+            assert_eq!(TokenKind::EOF, token.kind);
+            assert_eq!((3, 11), (token.span.line(), token.span.column()));
+            assert!(error.message().starts_with("Unexpected end of input."));
+        }
+        assert_eq!(ErrorKind::UnclosedParentheses, errors[0].kind());
+        assert_eq!(ErrorKind::UnclosedBrace, errors[1].kind());
+    }
+
+    #[test]
+    fn test_parser_recovers_after_errors() {
+        let source = r#"
+            var = 1;
+            print "fine";
+            (2 + ;
+            var ok = true;
+        "#;
+
+        let scanner = Lexer::new(source);
+        let (statements, errors): (Vec<_>, Vec<_>) =
+            scanner.advance_to_parsing().partition(Result::is_ok);
+
+        assert_eq!(2, statements.len());
+        assert_eq!(2, errors.len());
+    }
+
+    #[test]
+    fn test_parse_program_reports_every_error() {
+        let source = r#"
+            fun f() {
+                var = 1;
+                print "fine";
+                print 2
+            }
+            if (true) { print (; }
+            print "after";
+            var x = 1 print x;
+        "#;
+
+        let scanner = Lexer::new(source);
+        let (statements, errors) = scanner.advance_to_parsing().parse_program();
+
+        let kinds: Vec<_> = errors.iter().map(|error| error.kind()).collect();
+        assert_eq!(
+            vec![
+                ErrorKind::ExpectedIdentifier,
+                ErrorKind::MissingSemicolon,
+                ErrorKind::InvalidExpression,
+                ErrorKind::MissingSemicolon,
+            ],
+            kinds
+        );
+        // `print "after";` and `print x;`
+        assert_eq!(2, statements.len());
+    }
 }
@@ -0,0 +1,84 @@
+use thiserror::Error;
+
+use crate::lex::Token;
+
+/// The Error type returned by `Resolver`
+#[derive(Clone, Error, Debug)]
+#[error("{kind} Occurred at {token} {message}")]
+pub struct Error {
+    token: Box<Token>,
+    kind: ErrorKind,
+    message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    ReadLocalInOwnInitializer,
+    DuplicateDeclaration,
+    ReturnAtTopLevel,
+    ReturnValueFromInitializer,
+    ThisOutsideClass,
+    SuperOutsideClass,
+    SuperWithoutSuperclass,
+    ClassInheritsFromItself,
+}
+
+impl Error {
+    pub fn tokened(msg: impl Into<String>, token: Token, kind: ErrorKind) -> Self {
+        Error {
+            kind,
+            message: msg.into(),
+            token: Box::new(token),
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl ErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::ReadLocalInOwnInitializer => "R001",
+            ErrorKind::DuplicateDeclaration => "R002",
+            ErrorKind::ReturnAtTopLevel => "R003",
+            ErrorKind::ReturnValueFromInitializer => "R004",
+            ErrorKind::ThisOutsideClass => "R005",
+            ErrorKind::SuperOutsideClass => "R006",
+            ErrorKind::SuperWithoutSuperclass => "R007",
+            ErrorKind::ClassInheritsFromItself => "R008",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ErrorKind::ReadLocalInOwnInitializer => {
+                write!(f, "Can't read local variable in its own initializer.")
+            }
+            ErrorKind::DuplicateDeclaration => {
+                write!(f, "Already a variable with this name in this scope.")
+            }
+            ErrorKind::ReturnAtTopLevel => write!(f, "Can't return from top-level code."),
+            ErrorKind::ReturnValueFromInitializer => {
+                write!(f, "Can't return a value from an initializer.")
+            }
+            ErrorKind::ThisOutsideClass => write!(f, "Can't use 'this' outside of a class."),
+            ErrorKind::SuperOutsideClass => write!(f, "Can't use 'super' outside of a class."),
+            ErrorKind::SuperWithoutSuperclass => {
+                write!(f, "Can't use 'super' in a class with no superclass.")
+            }
+            ErrorKind::ClassInheritsFromItself => write!(f, "A class can't inherit from itself."),
+        }
+    }
+}
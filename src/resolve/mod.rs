@@ -0,0 +1,360 @@
+// Module declarations
+
+mod error;
+pub use error::{Error, ErrorKind};
+
+// resolver.rs
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use crate::ast::{visitor::StmtVisitor, *};
+use crate::lex::Token;
+use crate::Visitor;
+
+/// How many scopes away from its use each local variable was declared
+///
+/// Variables missing from `Locals` are globals.
+pub type Locals = HashMap<ExprId, usize>;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// A static analysis pass run between parsing and evaluation
+///
+/// Resolver walks every statement once, binding each variable reference to the scope
+/// it was declared in, and reports any errors that can be found without running the code.
+pub struct Resolver {
+    /// Block scopes, innermost last, each name maps to whether its initializer has been resolved yet
+    ///
+    /// The global scope is not tracked.
+    scopes: RefCell<Vec<HashMap<String, bool>>>,
+    locals: RefCell<Locals>,
+    errors: RefCell<Vec<Error>>,
+    current_function: Cell<FunctionType>,
+    current_class: Cell<ClassType>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            scopes: RefCell::new(Vec::new()),
+            locals: RefCell::new(HashMap::new()),
+            errors: RefCell::new(Vec::new()),
+            current_function: Cell::new(FunctionType::None),
+            current_class: Cell::new(ClassType::None),
+        }
+    }
+
+    /// Resolve a whole program, returning every error found rather than just the first
+    pub fn resolve(statements: &[Stmt]) -> Result<Locals, Vec<Error>> {
+        let resolver = Resolver::new();
+        for statement in statements {
+            resolver.visit_stmt(statement);
+        }
+
+        let errors = resolver.errors.into_inner();
+        if errors.is_empty() {
+            Ok(resolver.locals.into_inner())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn error(&self, token: &Token, kind: ErrorKind) {
+        self.errors
+            .borrow_mut()
+            .push(Error::tokened("", token.clone(), kind));
+    }
+
+    fn begin_scope(&self) {
+        self.scopes.borrow_mut().push(HashMap::new());
+    }
+
+    fn end_scope(&self) {
+        self.scopes.borrow_mut().pop();
+    }
+
+    /// Add a name to the innermost scope, marking it as not ready to be read yet
+    fn declare(&self, name: &Token) {
+        let duplicate = match self.scopes.borrow_mut().last_mut() {
+            Some(scope) => scope.insert(name.name().to_string(), false).is_some(),
+            None => false,
+        };
+        if duplicate {
+            self.error(name, ErrorKind::DuplicateDeclaration);
+        }
+    }
+
+    /// Mark a declared name as ready to be read
+    fn define(&self, name: &str) {
+        if let Some(scope) = self.scopes.borrow_mut().last_mut() {
+            scope.insert(name.to_string(), true);
+        }
+    }
+
+    fn resolve_local(&self, id: ExprId, name: &str) {
+        let depth = self
+            .scopes
+            .borrow()
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(name));
+        if let Some(depth) = depth {
+            self.locals.borrow_mut().insert(id, depth);
+        }
+    }
+
+    fn resolve_function(&self, function: &Function, function_type: FunctionType) {
+        let enclosing_function = self.current_function.replace(function_type);
+
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param);
+            self.define(param.name());
+        }
+        for statement in &function.body {
+            self.visit_stmt(statement);
+        }
+        self.end_scope();
+
+        self.current_function.set(enclosing_function);
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Resolver::new()
+    }
+}
+
+impl StmtVisitor<()> for Resolver {
+    fn visit_expr_stmt(&self, stmt: &ExprStmt) {
+        self.visit_expr(&stmt.0);
+    }
+    fn visit_print_stmt(&self, stmt: &PrintStmt) {
+        self.visit_expr(&stmt.0);
+    }
+    fn visit_var_stmt(&self, stmt: &Var) {
+        self.declare(&stmt.name);
+        if let Some(initializer) = &stmt.initializer {
+            self.visit_expr(initializer);
+        }
+        self.define(stmt.name.name());
+    }
+    fn visit_block(&self, block: &Block) {
+        self.begin_scope();
+        for statement in &block.statements {
+            self.visit_stmt(statement);
+        }
+        self.end_scope();
+    }
+    fn visit_if(&self, stmt: &If) {
+        self.visit_expr(&stmt.condition);
+        self.visit_stmt(&stmt.then_branch);
+        if let Some(else_branch) = &stmt.else_branch {
+            self.visit_stmt(else_branch);
+        }
+    }
+    fn visit_while(&self, stmt: &While) {
+        self.visit_expr(&stmt.condition);
+        self.visit_stmt(&stmt.body);
+        if let Some(increment) = &stmt.increment {
+            self.visit_expr(increment);
+        }
+    }
+    fn visit_break(&self, _stmt: &Break) {}
+    fn visit_continue(&self, _stmt: &Continue) {}
+    fn visit_function(&self, stmt: &Function) {
+        // define the name eagerly so the function can refer to itself recursively
+        self.declare(&stmt.name);
+        self.define(stmt.name.name());
+        self.resolve_function(stmt, FunctionType::Function);
+    }
+    fn visit_return(&self, stmt: &Return) {
+        if self.current_function.get() == FunctionType::None {
+            self.error(&stmt.keyword, ErrorKind::ReturnAtTopLevel);
+        }
+        if let Some(value) = &stmt.value {
+            if self.current_function.get() == FunctionType::Initializer {
+                self.error(&stmt.keyword, ErrorKind::ReturnValueFromInitializer);
+            }
+            self.visit_expr(value);
+        }
+    }
+    fn visit_class(&self, stmt: &Class) {
+        let enclosing_class = self.current_class.replace(ClassType::Class);
+
+        self.declare(&stmt.name);
+        self.define(stmt.name.name());
+
+        if let Some(superclass) = &stmt.superclass {
+            if superclass.name.name() == stmt.name.name() {
+                self.error(&superclass.name, ErrorKind::ClassInheritsFromItself);
+            }
+            self.current_class.set(ClassType::Subclass);
+            self.visit_variable(superclass);
+
+            self.begin_scope();
+            self.define("super");
+        }
+
+        self.begin_scope();
+        self.define("this");
+        for method in &stmt.methods {
+            let function_type = if method.name.name() == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, function_type);
+        }
+        self.end_scope();
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class.set(enclosing_class);
+    }
+}
+
+impl Visitor<()> for Resolver {
+    fn visit_grouping(&self, grouping: &Grouping) {
+        self.visit_expr(&grouping.0);
+    }
+    fn visit_binary(&self, binary: &Binary) {
+        self.visit_expr(&binary.left);
+        self.visit_expr(&binary.right);
+    }
+    fn visit_unary(&self, unary: &Unary) {
+        self.visit_expr(&unary.right);
+    }
+    fn visit_literal(&self, _literal: &LiteralValue) {}
+    fn visit_variable(&self, variable: &Variable) {
+        let declared_but_not_defined = self
+            .scopes
+            .borrow()
+            .last()
+            .and_then(|scope| scope.get(variable.name.name()))
+            == Some(&false);
+        if declared_but_not_defined {
+            self.error(&variable.name, ErrorKind::ReadLocalInOwnInitializer);
+        }
+
+        self.resolve_local(variable.id, variable.name.name());
+    }
+    fn visit_assign(&self, assign: &Assign) {
+        self.visit_expr(&assign.value);
+        self.resolve_local(assign.id, assign.name.name());
+    }
+    fn visit_logical(&self, logical: &Logical) {
+        self.visit_expr(&logical.left);
+        self.visit_expr(&logical.right);
+    }
+    fn visit_call(&self, call: &Call) {
+        self.visit_expr(&call.callee);
+        for argument in &call.arguments {
+            self.visit_expr(argument);
+        }
+    }
+    fn visit_get(&self, get: &Get) {
+        self.visit_expr(&get.object);
+    }
+    fn visit_set(&self, set: &Set) {
+        self.visit_expr(&set.value);
+        self.visit_expr(&set.object);
+    }
+    fn visit_this(&self, this: &This) {
+        if self.current_class.get() == ClassType::None {
+            self.error(&this.keyword, ErrorKind::ThisOutsideClass);
+            return;
+        }
+        self.resolve_local(this.id, "this");
+    }
+    fn visit_super(&self, super_method: &Super) {
+        match self.current_class.get() {
+            ClassType::None => self.error(&super_method.keyword, ErrorKind::SuperOutsideClass),
+            ClassType::Class => {
+                self.error(&super_method.keyword, ErrorKind::SuperWithoutSuperclass)
+            }
+            ClassType::Subclass => self.resolve_local(super_method.id, "super"),
+        }
+    }
+    fn visit_interpolation(&self, interpolation: &Interpolation) {
+        for part in &interpolation.parts {
+            self.visit_expr(part);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ErrorKind, Resolver};
+    use crate::ast::Stmt;
+    use crate::lex::Lexer;
+
+    fn resolve_errors(source: &str) -> Vec<ErrorKind> {
+        let statements = Lexer::new(source)
+            .advance_to_parsing()
+            .collect::<Result<Vec<Stmt>, _>>()
+            .expect("Error while parsing!");
+
+        match Resolver::resolve(&statements) {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.iter().map(|e| e.kind().clone()).collect(),
+        }
+    }
+
+    #[test]
+    fn valid_programs_resolve() {
+        let errors = resolve_errors(
+            r#"
+            var a = 1;
+            var a = a;
+            fun f(x) { var y = x; { var y = 2; } return y; }
+            class A { init() { this.a = 1; return; } }
+            class B < A { init() { super.init(); } }
+            "#,
+        );
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn every_error_is_collected() {
+        let errors = resolve_errors(
+            r#"
+            return 1;
+            print this;
+            { var a = 1; var a = 2; }
+            fun f() { var b = b; }
+            class A { init() { return 1; } method() { super.method(); } }
+            class B < B {}
+            "#,
+        );
+        assert_eq!(
+            vec![
+                ErrorKind::ReturnAtTopLevel,
+                ErrorKind::ThisOutsideClass,
+                ErrorKind::DuplicateDeclaration,
+                ErrorKind::ReadLocalInOwnInitializer,
+                ErrorKind::ReturnValueFromInitializer,
+                ErrorKind::SuperWithoutSuperclass,
+                ErrorKind::ClassInheritsFromItself,
+            ],
+            errors
+        );
+    }
+}
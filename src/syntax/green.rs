@@ -0,0 +1,164 @@
+use std::rc::Rc;
+
+use super::SyntaxKind;
+
+/// A leaf of the green tree, the exact text of a token or of some trivia
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: String,
+}
+
+impl GreenToken {
+    pub fn new(kind: SyntaxKind, text: impl Into<String>) -> Self {
+        GreenToken {
+            kind,
+            text: text.into(),
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// An immutable node of the green tree
+///
+/// Green nodes only know their kind, their length and their children, not where they are,
+/// so they can be shared between trees. `SyntaxNode` adds the positions and parent links.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    len: usize,
+    children: Vec<GreenElement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement {
+    /// Length of the text in bytes
+    pub fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len(),
+            GreenElement::Token(token) => token.text().len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        GreenNode {
+            kind,
+            len: children.iter().map(GreenElement::len).sum(),
+            children,
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    /// Length of the text in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    /// Write out the text of every token under this node, which is exactly the source it came from
+    pub fn write_text(&self, text: &mut String) {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => node.write_text(text),
+                GreenElement::Token(token) => text.push_str(token.text()),
+            }
+        }
+    }
+}
+
+/// Where a node can be started later, around everything added since, see `GreenNodeBuilder::start_node_at`
+#[derive(Debug, Clone, Copy)]
+pub struct Checkpoint(usize);
+
+/// Builds a green tree from the top down
+///
+/// Nodes are started and finished like brackets, with tokens added in between.
+#[derive(Debug, Default)]
+pub struct GreenNodeBuilder {
+    /// The nodes that are still open and the index of their first child in `children`
+    parents: Vec<(SyntaxKind, usize)>,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNodeBuilder {
+    pub fn new() -> Self {
+        GreenNodeBuilder::default()
+    }
+
+    pub fn start_node(&mut self, kind: SyntaxKind) {
+        self.parents.push((kind, self.children.len()));
+    }
+
+    pub fn token(&mut self, kind: SyntaxKind, text: &str) {
+        self.children
+            .push(GreenElement::Token(Rc::new(GreenToken::new(kind, text))));
+    }
+
+    pub fn finish_node(&mut self) {
+        let (kind, first_child) = self
+            .parents
+            .pop()
+            .expect("finish_node called without a matching start_node");
+        let children = self.children.split_off(first_child);
+        self.children
+            .push(GreenElement::Node(Rc::new(GreenNode::new(kind, children))));
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.children.len())
+    }
+
+    /// Start a node that wraps everything added since the checkpoint, like the left side of `a + b`
+    pub fn start_node_at(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        let Checkpoint(first_child) = checkpoint;
+        assert!(
+            first_child <= self.children.len(),
+            "checkpoint is no longer valid"
+        );
+        if let Some(&(_, parent_first_child)) = self.parents.last() {
+            assert!(
+                first_child >= parent_first_child,
+                "checkpoint is outside of the current node"
+            );
+        }
+        self.parents.push((kind, first_child));
+    }
+
+    /// Finish building, there must be exactly one node left
+    pub fn finish(mut self) -> Rc<GreenNode> {
+        assert!(self.parents.is_empty(), "some nodes were never finished");
+        assert_eq!(1, self.children.len(), "a tree has exactly one root");
+        match self.children.pop() {
+            Some(GreenElement::Node(node)) => node,
+            _ => panic!("the root of a tree must be a node"),
+        }
+    }
+}
@@ -0,0 +1,350 @@
+//! A lossless concrete syntax tree, for tools like formatters that have to keep the source as written
+//!
+//! Every byte of the source ends up in exactly one token of the tree: whitespace, comments and
+//! text that isn't valid Lox included. The tree is never refused, mistakes just turn into `ERROR`
+//! nodes, so to find out what's wrong use `Lexer::tokenize` and `Parser::parse_program` as usual.
+
+// Mod declarations
+
+mod green;
+pub use green::{Checkpoint, GreenElement, GreenNode, GreenNodeBuilder, GreenToken};
+
+mod parser;
+
+mod red;
+pub use red::{SyntaxElement, SyntaxNode, SyntaxToken};
+
+// syntax.rs
+
+use std::fmt;
+
+use crate::lex::{Lexer, TokenKind, TriviaKind};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum SyntaxKind {
+    /// A token from the lexer
+    TOKEN(TokenKind),
+
+    // Trivia.
+    WHITESPACE,
+    COMMENT,
+    DOC_COMMENT,
+    /// Text the lexer couldn't make into a token, like `#` or an unterminated string
+    ERROR_TOKEN,
+
+    // Nodes.
+    PROGRAM,
+    VAR_DECL,
+    FUN_DECL,
+    CLASS_DECL,
+    METHOD,
+    PARAM_LIST,
+    BLOCK,
+    EXPR_STMT,
+    PRINT_STMT,
+    IF_STMT,
+    WHILE_STMT,
+    FOR_STMT,
+    BREAK_STMT,
+    CONTINUE_STMT,
+    RETURN_STMT,
+    LITERAL,
+    VARIABLE,
+    GROUPING,
+    UNARY_EXPR,
+    BINARY_EXPR,
+    LOGICAL_EXPR,
+    ASSIGN_EXPR,
+    CALL_EXPR,
+    ARG_LIST,
+    GET_EXPR,
+    THIS_EXPR,
+    SUPER_EXPR,
+    INTERPOLATION,
+    /// Tokens the parser didn't expect where they are
+    ERROR,
+}
+
+impl SyntaxKind {
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::WHITESPACE
+                | SyntaxKind::COMMENT
+                | SyntaxKind::DOC_COMMENT
+                | SyntaxKind::ERROR_TOKEN
+        )
+    }
+}
+
+impl fmt::Display for SyntaxKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyntaxKind::TOKEN(kind) => write!(f, "{:?}", kind),
+            kind => write!(f, "{:?}", kind),
+        }
+    }
+}
+
+/// Build the lossless syntax tree of some source
+pub fn parse(source: &str) -> SyntaxNode {
+    let tokens = lossless_tokens(source);
+    SyntaxNode::new_root(parser::Parser::new(tokens).program())
+}
+
+/// Split the source into tokens and trivia with no gaps between them
+///
+/// The lexer skips whitespace and anything it reports an error for, so those are whatever is
+/// left between the tokens and comments it does give us.
+fn lossless_tokens(source: &str) -> Vec<(SyntaxKind, &str)> {
+    let lexer = Lexer::new(source).with_comments(true);
+    let mut pieces = Vec::new();
+    let mut errors = Vec::new();
+    for result in lexer.scan_borrowed() {
+        match result {
+            Ok(token) => {
+                for trivia in &token.trivia {
+                    let kind = match trivia.kind {
+                        TriviaKind::DocComment => SyntaxKind::DOC_COMMENT,
+                        TriviaKind::LineComment | TriviaKind::BlockComment => SyntaxKind::COMMENT,
+                    };
+                    pieces.push((kind, trivia.span.range()));
+                }
+                if token.kind != TokenKind::EOF {
+                    pieces.push((SyntaxKind::TOKEN(token.kind), token.span.range()));
+                }
+            }
+            Err(error) => errors.push(error.span().range()),
+        }
+    }
+    pieces.sort_by_key(|(_, range)| range.start);
+
+    let mut tokens = Vec::new();
+    let mut end = 0;
+    for (kind, range) in pieces {
+        // nothing should overlap, but if it does the first piece wins
+        if range.start < end {
+            continue;
+        }
+        gap_tokens(source, end..range.start, &errors, &mut tokens);
+        tokens.push((kind, &source[range.clone()]));
+        end = range.end;
+    }
+    gap_tokens(source, end..source.len(), &errors, &mut tokens);
+    tokens
+}
+
+/// Whitespace and error tokens for text the lexer skipped over
+fn gap_tokens<'src>(
+    source: &'src str,
+    gap: std::ops::Range<usize>,
+    errors: &[std::ops::Range<usize>],
+    tokens: &mut Vec<(SyntaxKind, &'src str)>,
+) {
+    let mut run: Option<(SyntaxKind, usize)> = None;
+    for (i, c) in source[gap.clone()].char_indices() {
+        let offset = gap.start + i;
+        let kind = if c.is_whitespace() && !errors.iter().any(|error| error.contains(&offset)) {
+            SyntaxKind::WHITESPACE
+        } else {
+            SyntaxKind::ERROR_TOKEN
+        };
+        match run {
+            Some((run_kind, _)) if run_kind == kind => {}
+            Some((run_kind, start)) => {
+                tokens.push((run_kind, &source[start..offset]));
+                run = Some((kind, offset));
+            }
+            None => run = Some((kind, offset)),
+        }
+    }
+    if let Some((kind, start)) = run {
+        tokens.push((kind, &source[start..gap.end]));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, SyntaxElement, SyntaxKind, SyntaxNode};
+    use crate::ast::{ExprStmt, Stmt};
+    use crate::lex::TokenKind;
+    use crate::printer::DebugPrinter;
+    use crate::Lexer;
+
+    /// Write a binary expression of variables like `DebugPrinter` does, e.g. `(- (- a b) c)`
+    fn print_operators(node: &SyntaxNode) -> String {
+        let mut operator = String::new();
+        let mut operands = Vec::new();
+        for child in node.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(child) => operands.push(print_operators(&child)),
+                SyntaxElement::Token(token) if !token.is_trivia() => {
+                    operator = token.text().to_string()
+                }
+                SyntaxElement::Token(_) => {}
+            }
+        }
+        match node.kind() {
+            SyntaxKind::BINARY_EXPR | SyntaxKind::LOGICAL_EXPR => {
+                format!("({} {})", operator, operands.join(" "))
+            }
+            _ => operator,
+        }
+    }
+
+    #[test]
+    fn test_every_byte_is_in_the_tree() {
+        let sources = [
+            "",
+            "   \n\t ",
+            "var a = 1; // one\n/* two /* nested */ */ print a;\n",
+            "/// Adds\nfun add(a, b) {\n    return a + b;\n}\n\nclass B < A { init() { super.init(); this.x = 1; } }\n",
+            "for (var i = 0; i < 10; i = i + 1) { if (i == 3) continue; else break; }",
+            r#"print "x ${a + "${b}"} y" + r"C:\path" + "\u{1F600}";"#,
+            "var = ; print ; ) } fun ( { 1 +",
+            "var a = #; @ \"unterminated\n string",
+            "print \"bad \\q escape\"; var ü = 0x_; /* never closed",
+            "print \"${a\";",
+        ];
+        for source in sources.iter() {
+            let tree = parse(source);
+            assert_eq!(*source, tree.text(), "{}", tree.debug_tree());
+            assert_eq!(0..source.len(), tree.text_range());
+
+            let mut end = 0;
+            for token in tree.tokens() {
+                assert_eq!(end, token.text_range().start, "{}", tree.debug_tree());
+                assert_eq!(&source[token.text_range()], token.text());
+                end = token.text_range().end;
+            }
+            assert_eq!(source.len(), end);
+        }
+    }
+
+    #[test]
+    fn test_tree_shape() {
+        let tree = parse("var a = 1 + 2 * (b); // three\n");
+
+        assert_eq!(
+            r#"PROGRAM@0..30
+  VAR_DECL@0..20
+    VAR@0..3 "var"
+    WHITESPACE@3..4 " "
+    IDENTIFIER@4..5 "a"
+    WHITESPACE@5..6 " "
+    EQUAL@6..7 "="
+    WHITESPACE@7..8 " "
+    BINARY_EXPR@8..19
+      LITERAL@8..9
+        NUMBER@8..9 "1"
+      WHITESPACE@9..10 " "
+      PLUS@10..11 "+"
+      WHITESPACE@11..12 " "
+      BINARY_EXPR@12..19
+        LITERAL@12..13
+          NUMBER@12..13 "2"
+        WHITESPACE@13..14 " "
+        STAR@14..15 "*"
+        WHITESPACE@15..16 " "
+        GROUPING@16..19
+          LEFT_PAREN@16..17 "("
+          VARIABLE@17..18
+            IDENTIFIER@17..18 "b"
+          RIGHT_PAREN@18..19 ")"
+    SEMICOLON@19..20 ";"
+  WHITESPACE@20..21 " "
+  COMMENT@21..29 "// three"
+  WHITESPACE@29..30 "\n"
+"#,
+            tree.debug_tree()
+        );
+    }
+
+    #[test]
+    fn test_doc_comments_belong_to_their_declaration() {
+        let tree = parse("// not docs\n\n/// Says hi\nfun hi() {}");
+
+        let function = tree.children().remove(0);
+        assert_eq!(SyntaxKind::FUN_DECL, function.kind());
+        assert_eq!("/// Says hi\nfun hi() {}", function.text());
+        assert_eq!(SyntaxKind::COMMENT, tree.token_at_offset(0).unwrap().kind());
+        let name = tree.token_at_offset(29).unwrap();
+        assert_eq!("hi", name.text());
+        assert_eq!(function, name.parent());
+    }
+
+    #[test]
+    fn test_mistakes_become_error_nodes() {
+        let tree = parse("print 1 + ; , var a = #;");
+
+        let kinds = tree
+            .descendants()
+            .iter()
+            .map(|node| node.kind())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                SyntaxKind::PROGRAM,
+                SyntaxKind::PRINT_STMT,
+                SyntaxKind::BINARY_EXPR,
+                SyntaxKind::LITERAL,
+                SyntaxKind::ERROR,
+                SyntaxKind::VAR_DECL,
+            ],
+            kinds
+        );
+        let tokens = tree
+            .tokens()
+            .into_iter()
+            .filter(|token| !token.is_trivia() || token.kind() == SyntaxKind::ERROR_TOKEN)
+            .map(|token| token.kind())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                SyntaxKind::TOKEN(TokenKind::PRINT),
+                SyntaxKind::TOKEN(TokenKind::NUMBER),
+                SyntaxKind::TOKEN(TokenKind::PLUS),
+                SyntaxKind::TOKEN(TokenKind::SEMICOLON),
+                SyntaxKind::TOKEN(TokenKind::COMMA),
+                SyntaxKind::TOKEN(TokenKind::VAR),
+                SyntaxKind::TOKEN(TokenKind::IDENTIFIER),
+                SyntaxKind::TOKEN(TokenKind::EQUAL),
+                SyntaxKind::ERROR_TOKEN,
+                SyntaxKind::TOKEN(TokenKind::SEMICOLON),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn test_operators_group_like_the_ast() {
+        let sources = [
+            "a - b - c;",
+            "a / b * c - d + e;",
+            "a == b != c;",
+            "a < b <= c > d;",
+            "a or b or c and d and e;",
+        ];
+        for source in sources.iter() {
+            let statement = Lexer::new(*source)
+                .advance_to_parsing()
+                .next()
+                .unwrap()
+                .unwrap();
+            let expr = match statement {
+                Stmt::ExprStmt(ExprStmt(expr)) => expr,
+                _ => panic!("Expected an expression statement"),
+            };
+
+            let tree = parse(source);
+            let statement = tree.children().remove(0);
+            let node = statement.children().remove(0);
+            assert_eq!(DebugPrinter::print(&expr), print_operators(&node));
+        }
+        assert_eq!(
+            "(- (- a b) c)",
+            print_operators(&parse("a - b - c;").children()[0].children()[0])
+        );
+    }
+}
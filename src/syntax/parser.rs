@@ -0,0 +1,473 @@
+use std::rc::Rc;
+
+use super::green::{Checkpoint, GreenNode, GreenNodeBuilder};
+use super::SyntaxKind;
+use crate::lex::TokenKind;
+
+/// Builds the concrete syntax tree, following the same grammar as `crate::Parser`
+///
+/// It never gives up: a missing token is just left out and an unexpected one is wrapped in an
+/// `ERROR` node, so that every token ends up somewhere. Trivia goes into whichever node is open
+/// when the next real token is reached, except doc comments which go with their declaration.
+pub(super) struct Parser<'src> {
+    tokens: Vec<(SyntaxKind, &'src str)>,
+    /// Index of the next token (or trivia) to add to the tree
+    position: usize,
+    builder: GreenNodeBuilder,
+}
+
+impl<'src> Parser<'src> {
+    pub(super) fn new(tokens: Vec<(SyntaxKind, &'src str)>) -> Self {
+        Parser {
+            tokens,
+            position: 0,
+            builder: GreenNodeBuilder::new(),
+        }
+    }
+
+    pub(super) fn program(mut self) -> Rc<GreenNode> {
+        self.builder.start_node(SyntaxKind::PROGRAM);
+        while !self.at(TokenKind::EOF) {
+            self.declaration();
+        }
+        self.eat_trivia();
+        self.builder.finish_node();
+        self.builder.finish()
+    }
+
+    fn declaration(&mut self) {
+        match self.current() {
+            TokenKind::VAR => self.var_declaration(),
+            TokenKind::FUN => {
+                self.start_declaration(SyntaxKind::FUN_DECL);
+                self.bump();
+                self.function();
+                self.builder.finish_node();
+            }
+            TokenKind::CLASS => self.class_declaration(),
+            _ => self.statement(),
+        }
+    }
+
+    fn class_declaration(&mut self) {
+        self.start_declaration(SyntaxKind::CLASS_DECL);
+        self.bump();
+        self.expect(TokenKind::IDENTIFIER);
+        if self.eat(TokenKind::LESS) {
+            self.expect(TokenKind::IDENTIFIER);
+        }
+        if self.expect(TokenKind::LEFT_BRACE) {
+            while !self.at(TokenKind::RIGHT_BRACE) && !self.at(TokenKind::EOF) {
+                if self.at(TokenKind::IDENTIFIER) {
+                    self.start_declaration(SyntaxKind::METHOD);
+                    self.function();
+                    self.builder.finish_node();
+                } else {
+                    self.error();
+                }
+            }
+            self.expect(TokenKind::RIGHT_BRACE);
+        }
+        self.builder.finish_node();
+    }
+
+    /// The name, parameters and body of a function or method
+    fn function(&mut self) {
+        self.expect(TokenKind::IDENTIFIER);
+        if self.at(TokenKind::LEFT_PAREN) {
+            self.start(SyntaxKind::PARAM_LIST);
+            self.bump();
+            if !self.at(TokenKind::RIGHT_PAREN) {
+                self.expect(TokenKind::IDENTIFIER);
+                while self.eat(TokenKind::COMMA) {
+                    self.expect(TokenKind::IDENTIFIER);
+                }
+            }
+            self.expect(TokenKind::RIGHT_PAREN);
+            self.builder.finish_node();
+        }
+        if self.at(TokenKind::LEFT_BRACE) {
+            self.block();
+        }
+    }
+
+    fn var_declaration(&mut self) {
+        self.start_declaration(SyntaxKind::VAR_DECL);
+        self.bump();
+        self.expect(TokenKind::IDENTIFIER);
+        if self.eat(TokenKind::EQUAL) {
+            self.expression();
+        }
+        self.expect(TokenKind::SEMICOLON);
+        self.builder.finish_node();
+    }
+
+    fn statement(&mut self) {
+        match self.current() {
+            TokenKind::PRINT => self.keyword_statement(SyntaxKind::PRINT_STMT, true),
+            TokenKind::RETURN => {
+                let has_value = !self.nth_at(1, TokenKind::SEMICOLON);
+                self.keyword_statement(SyntaxKind::RETURN_STMT, has_value)
+            }
+            TokenKind::BREAK => self.keyword_statement(SyntaxKind::BREAK_STMT, false),
+            TokenKind::CONTINUE => self.keyword_statement(SyntaxKind::CONTINUE_STMT, false),
+            TokenKind::LEFT_BRACE => self.block(),
+            TokenKind::IF => {
+                self.start(SyntaxKind::IF_STMT);
+                self.bump();
+                self.condition();
+                self.statement();
+                if self.eat(TokenKind::ELSE) {
+                    self.statement();
+                }
+                self.builder.finish_node();
+            }
+            TokenKind::WHILE => {
+                self.start(SyntaxKind::WHILE_STMT);
+                self.bump();
+                self.condition();
+                self.statement();
+                self.builder.finish_node();
+            }
+            TokenKind::FOR => self.for_statement(),
+            _ if self.at_expression() => {
+                self.start(SyntaxKind::EXPR_STMT);
+                self.expression();
+                self.expect(TokenKind::SEMICOLON);
+                self.builder.finish_node();
+            }
+            // can't start a statement, skip over it so we always make progress
+            _ => self.error(),
+        }
+    }
+
+    /// `print value;`, `return value;`, `break;` and `continue;`
+    fn keyword_statement(&mut self, kind: SyntaxKind, has_value: bool) {
+        self.start(kind);
+        self.bump();
+        if has_value {
+            self.expression();
+        }
+        self.expect(TokenKind::SEMICOLON);
+        self.builder.finish_node();
+    }
+
+    /// The parenthesised condition of an `if` or `while`
+    fn condition(&mut self) {
+        self.expect(TokenKind::LEFT_PAREN);
+        self.expression();
+        self.expect(TokenKind::RIGHT_PAREN);
+    }
+
+    fn for_statement(&mut self) {
+        self.start(SyntaxKind::FOR_STMT);
+        self.bump();
+        self.expect(TokenKind::LEFT_PAREN);
+        match self.current() {
+            TokenKind::SEMICOLON => self.bump(),
+            TokenKind::VAR => self.var_declaration(),
+            _ => {
+                self.start(SyntaxKind::EXPR_STMT);
+                self.expression();
+                self.expect(TokenKind::SEMICOLON);
+                self.builder.finish_node();
+            }
+        }
+        if !self.at(TokenKind::SEMICOLON) {
+            self.expression();
+        }
+        self.expect(TokenKind::SEMICOLON);
+        if !self.at(TokenKind::RIGHT_PAREN) {
+            self.expression();
+        }
+        self.expect(TokenKind::RIGHT_PAREN);
+        self.statement();
+        self.builder.finish_node();
+    }
+
+    fn block(&mut self) {
+        self.start(SyntaxKind::BLOCK);
+        self.bump();
+        while !self.at(TokenKind::RIGHT_BRACE) && !self.at(TokenKind::EOF) {
+            self.declaration();
+        }
+        self.expect(TokenKind::RIGHT_BRACE);
+        self.builder.finish_node();
+    }
+
+    fn expression(&mut self) {
+        let checkpoint = self.checkpoint();
+        self.binary(0);
+        if self.at(TokenKind::EQUAL) {
+            self.builder
+                .start_node_at(checkpoint, SyntaxKind::ASSIGN_EXPR);
+            self.bump();
+            self.expression();
+            self.builder.finish_node();
+        }
+    }
+
+    /// Binary operators that bind at least as tightly as `min_precedence`
+    fn binary(&mut self, min_precedence: u8) {
+        let checkpoint = self.checkpoint();
+        self.unary();
+        loop {
+            let (precedence, kind) = match self.current() {
+                TokenKind::OR => (1, SyntaxKind::LOGICAL_EXPR),
+                TokenKind::AND => (2, SyntaxKind::LOGICAL_EXPR),
+                TokenKind::EQUAL_EQUAL | TokenKind::BANG_EQUAL => (3, SyntaxKind::BINARY_EXPR),
+                TokenKind::GREATER
+                | TokenKind::GREATER_EQUAL
+                | TokenKind::LESS
+                | TokenKind::LESS_EQUAL => (4, SyntaxKind::BINARY_EXPR),
+                TokenKind::PLUS | TokenKind::MINUS => (5, SyntaxKind::BINARY_EXPR),
+                TokenKind::STAR | TokenKind::SLASH => (6, SyntaxKind::BINARY_EXPR),
+                _ => break,
+            };
+            if precedence < min_precedence {
+                break;
+            }
+            self.builder.start_node_at(checkpoint, kind);
+            self.bump();
+            self.binary(precedence + 1);
+            self.builder.finish_node();
+        }
+    }
+
+    fn unary(&mut self) {
+        if self.at(TokenKind::BANG) || self.at(TokenKind::MINUS) {
+            self.start(SyntaxKind::UNARY_EXPR);
+            self.bump();
+            self.unary();
+            self.builder.finish_node();
+        } else {
+            self.call();
+        }
+    }
+
+    fn call(&mut self) {
+        let checkpoint = self.checkpoint();
+        self.primary();
+        loop {
+            if self.at(TokenKind::LEFT_PAREN) {
+                self.builder
+                    .start_node_at(checkpoint, SyntaxKind::CALL_EXPR);
+                self.arguments();
+                self.builder.finish_node();
+            } else if self.at(TokenKind::DOT) {
+                self.builder.start_node_at(checkpoint, SyntaxKind::GET_EXPR);
+                self.bump();
+                self.expect(TokenKind::IDENTIFIER);
+                self.builder.finish_node();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn arguments(&mut self) {
+        self.start(SyntaxKind::ARG_LIST);
+        self.bump();
+        if !self.at(TokenKind::RIGHT_PAREN) {
+            self.expression();
+            while self.eat(TokenKind::COMMA) {
+                self.expression();
+            }
+        }
+        self.expect(TokenKind::RIGHT_PAREN);
+        self.builder.finish_node();
+    }
+
+    fn primary(&mut self) {
+        match self.current() {
+            TokenKind::TRUE
+            | TokenKind::FALSE
+            | TokenKind::NIL
+            | TokenKind::NUMBER
+            | TokenKind::STRING => self.wrap(SyntaxKind::LITERAL),
+            TokenKind::IDENTIFIER => self.wrap(SyntaxKind::VARIABLE),
+            TokenKind::THIS => self.wrap(SyntaxKind::THIS_EXPR),
+            TokenKind::SUPER => {
+                self.start(SyntaxKind::SUPER_EXPR);
+                self.bump();
+                self.expect(TokenKind::DOT);
+                self.expect(TokenKind::IDENTIFIER);
+                self.builder.finish_node();
+            }
+            TokenKind::LEFT_PAREN => {
+                self.start(SyntaxKind::GROUPING);
+                self.bump();
+                self.expression();
+                self.expect(TokenKind::RIGHT_PAREN);
+                self.builder.finish_node();
+            }
+            TokenKind::INTERPOLATION => self.interpolation(),
+            // leave anything that could end the statement for the statement to deal with
+            TokenKind::SEMICOLON
+            | TokenKind::RIGHT_PAREN
+            | TokenKind::RIGHT_BRACE
+            | TokenKind::EOF
+            | TokenKind::VAR
+            | TokenKind::FUN
+            | TokenKind::CLASS
+            | TokenKind::PRINT
+            | TokenKind::RETURN
+            | TokenKind::IF
+            | TokenKind::WHILE
+            | TokenKind::FOR
+            | TokenKind::BREAK
+            | TokenKind::CONTINUE => {}
+            _ => self.error(),
+        }
+    }
+
+    /// `"a ${b} c"` is the tokens `"a ${`, the expression `b` and then `} c"`
+    fn interpolation(&mut self) {
+        self.start(SyntaxKind::INTERPOLATION);
+        self.bump();
+        loop {
+            self.expression();
+            if !self.at(TokenKind::INTERPOLATION) || !self.current_text().starts_with('}') {
+                break;
+            }
+            let continues = self.current_text().ends_with("${");
+            self.bump();
+            if !continues {
+                break;
+            }
+        }
+        self.builder.finish_node();
+    }
+
+    // Helpers
+
+    /// Position of the next token that isn't trivia, if there is one
+    fn nth_position(&self, n: usize) -> Option<usize> {
+        self.tokens
+            .iter()
+            .enumerate()
+            .skip(self.position)
+            .filter(|(_, (kind, _))| !kind.is_trivia())
+            .map(|(position, _)| position)
+            .nth(n)
+    }
+
+    /// The kind of the `n`th token after trivia, `EOF` once we're past the last one
+    fn nth(&self, n: usize) -> TokenKind {
+        match self.nth_position(n).map(|position| self.tokens[position].0) {
+            Some(SyntaxKind::TOKEN(kind)) => kind,
+            _ => TokenKind::EOF,
+        }
+    }
+
+    fn current(&self) -> TokenKind {
+        self.nth(0)
+    }
+
+    fn current_text(&self) -> &'src str {
+        self.nth_position(0)
+            .map_or("", |position| self.tokens[position].1)
+    }
+
+    fn at(&self, kind: TokenKind) -> bool {
+        self.current() == kind
+    }
+
+    fn nth_at(&self, n: usize, kind: TokenKind) -> bool {
+        self.nth(n) == kind
+    }
+
+    fn at_expression(&self) -> bool {
+        matches!(
+            self.current(),
+            TokenKind::TRUE
+                | TokenKind::FALSE
+                | TokenKind::NIL
+                | TokenKind::NUMBER
+                | TokenKind::STRING
+                | TokenKind::INTERPOLATION
+                | TokenKind::IDENTIFIER
+                | TokenKind::THIS
+                | TokenKind::SUPER
+                | TokenKind::LEFT_PAREN
+                | TokenKind::BANG
+                | TokenKind::MINUS
+        )
+    }
+
+    /// Add the trivia before the next token to the node that's open now
+    fn eat_trivia(&mut self) {
+        while let Some(&(kind, text)) = self.tokens.get(self.position) {
+            if !kind.is_trivia() {
+                break;
+            }
+            self.builder.token(kind, text);
+            self.position += 1;
+        }
+    }
+
+    /// Add the next token, and the trivia before it
+    fn bump(&mut self) {
+        self.eat_trivia();
+        if let Some(&(kind, text)) = self.tokens.get(self.position) {
+            self.builder.token(kind, text);
+            self.position += 1;
+        }
+    }
+
+    fn eat(&mut self, kind: TokenKind) -> bool {
+        let found = self.at(kind);
+        if found {
+            self.bump();
+        }
+        found
+    }
+
+    /// Add the token if it's there, otherwise it's just missing from the tree
+    fn expect(&mut self, kind: TokenKind) -> bool {
+        self.eat(kind)
+    }
+
+    /// Start a node at the next token, the trivia before it stays in the parent
+    fn start(&mut self, kind: SyntaxKind) {
+        self.eat_trivia();
+        self.builder.start_node(kind);
+    }
+
+    /// Start a node that takes the doc comments right before it along
+    fn start_declaration(&mut self, kind: SyntaxKind) {
+        let next = self.nth_position(0).unwrap_or(self.tokens.len());
+        // the docs are the doc comments at the end of the trivia, with only whitespace between them
+        let mut docs = next;
+        for position in (self.position..next).rev() {
+            match self.tokens[position].0 {
+                SyntaxKind::DOC_COMMENT => docs = position,
+                SyntaxKind::WHITESPACE => {}
+                _ => break,
+            }
+        }
+        while self.position < docs {
+            let (kind, text) = self.tokens[self.position];
+            self.builder.token(kind, text);
+            self.position += 1;
+        }
+        self.builder.start_node(kind);
+    }
+
+    fn checkpoint(&mut self) -> Checkpoint {
+        self.eat_trivia();
+        self.builder.checkpoint()
+    }
+
+    /// A node around just the next token
+    fn wrap(&mut self, kind: SyntaxKind) {
+        self.start(kind);
+        self.bump();
+        self.builder.finish_node();
+    }
+
+    /// Skip a token the grammar has no place for
+    fn error(&mut self) {
+        self.wrap(SyntaxKind::ERROR);
+    }
+}
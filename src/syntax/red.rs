@@ -0,0 +1,225 @@
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+use super::green::{GreenElement, GreenNode, GreenToken};
+use super::SyntaxKind;
+
+/// A node of the red tree, a green node plus where it is in the file and who its parent is
+///
+/// These are cheap to clone and made on demand while walking down from the root,
+/// the green tree underneath is never copied.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData {
+    green: Rc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    /// Byte offset of the start of the node in the source
+    offset: usize,
+}
+
+/// A token or some trivia in the red tree, it always has a parent node
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    parent: SyntaxNode,
+    offset: usize,
+}
+
+#[derive(Clone, Debug)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Rc<GreenNode>) -> Self {
+        SyntaxNode(Rc::new(NodeData {
+            green,
+            parent: None,
+            offset: 0,
+        }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind()
+    }
+
+    pub fn green(&self) -> &Rc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    /// Byte range of the node in the source, including any trivia inside it
+    pub fn text_range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.len()
+    }
+
+    /// The source text under this node, for the root this is the whole file
+    pub fn text(&self) -> String {
+        let mut text = String::with_capacity(self.0.green.len());
+        self.0.green.write_text(&mut text);
+        text
+    }
+
+    pub fn children_with_tokens(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        self.0
+            .green
+            .children()
+            .iter()
+            .map(|child| {
+                let element = match child {
+                    GreenElement::Node(green) => {
+                        SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                            green: green.clone(),
+                            parent: Some(self.clone()),
+                            offset,
+                        })))
+                    }
+                    GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                        green: green.clone(),
+                        parent: self.clone(),
+                        offset,
+                    }),
+                };
+                offset += child.len();
+                element
+            })
+            .collect()
+    }
+
+    /// The child nodes, leaving out tokens
+    pub fn children(&self) -> Vec<SyntaxNode> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect()
+    }
+
+    /// This node and every node under it, in source order
+    pub fn descendants(&self) -> Vec<SyntaxNode> {
+        let mut nodes = vec![self.clone()];
+        for child in self.children() {
+            nodes.extend(child.descendants());
+        }
+        nodes
+    }
+
+    /// Every token and piece of trivia under this node, in source order
+    ///
+    /// Their text joined together is exactly `text()`.
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = Vec::new();
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    /// The token (or trivia) that contains the byte at `offset`, for finding what's under a cursor
+    pub fn token_at_offset(&self, offset: usize) -> Option<SyntaxToken> {
+        self.tokens()
+            .into_iter()
+            .find(|token| token.text_range().contains(&offset))
+    }
+
+    /// An indented outline of the tree with the range of every node and token, handy for tests
+    pub fn debug_tree(&self) -> String {
+        let mut tree = String::new();
+        self.write_tree(&mut tree, 0);
+        tree
+    }
+
+    fn write_tree(&self, tree: &mut String, depth: usize) {
+        let range = self.text_range();
+        tree.push_str(&format!(
+            "{:indent$}{}@{}..{}\n",
+            "",
+            self.kind(),
+            range.start,
+            range.end,
+            indent = depth * 2
+        ));
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) => node.write_tree(tree, depth + 1),
+                SyntaxElement::Token(token) => tree.push_str(&format!(
+                    "{:indent$}{:?}\n",
+                    "",
+                    token,
+                    indent = (depth + 1) * 2
+                )),
+            }
+        }
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind()
+    }
+
+    pub fn text(&self) -> &str {
+        self.green.text()
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text().len()
+    }
+
+    pub fn parent(&self) -> SyntaxNode {
+        self.parent.clone()
+    }
+
+    /// Whitespace, comments and text the lexer couldn't make sense of
+    pub fn is_trivia(&self) -> bool {
+        self.kind().is_trivia()
+    }
+}
+
+impl PartialEq for SyntaxNode {
+    /// The same node of the same tree, not just nodes that look alike
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0.green, &other.0.green) && self.0.offset == other.0.offset
+    }
+}
+
+impl Eq for SyntaxNode {}
+
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let range = self.text_range();
+        write!(f, "{}@{}..{}", self.kind(), range.start, range.end)
+    }
+}
+
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let range = self.text_range();
+        write!(
+            f,
+            "{}@{}..{} {:?}",
+            self.kind(),
+            range.start,
+            range.end,
+            self.text()
+        )
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
@@ -0,0 +1,110 @@
+use thiserror::Error;
+
+use crate::lex::Span;
+
+/// The Error type returned by `Vm`
+///
+/// Located by the span of the token the failing instruction was compiled from.
+#[derive(Clone, Error, Debug)]
+#[error("{kind} Occurred at line {} {message}", .span.line())]
+pub struct Error {
+    span: Span,
+    kind: ErrorKind,
+    message: String,
+}
+
+#[derive(Clone, Debug)]
+pub enum ErrorKind {
+    BadNumericalNegation,
+    BadAddition,
+    BadSubtraction,
+    BadMultiplication,
+    BadDivision,
+    BadStringRepCount,
+    BadComparison,
+    UndefinedVariable(String),
+    NotCallable,
+    ArityMismatch { expected: usize, found: usize },
+    UndefinedProperty(String),
+    OnlyInstancesHaveProperties,
+    SuperclassMustBeClass,
+    NativeFunctionError,
+    StackOverflow,
+    InvalidBytecode,
+}
+
+impl Error {
+    pub fn spanned(msg: impl Into<String>, span: Span, kind: ErrorKind) -> Self {
+        Error {
+            kind,
+            message: msg.into(),
+            span,
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub fn line(&self) -> usize {
+        self.span.line()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl ErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::BadNumericalNegation => "E001",
+            ErrorKind::BadAddition => "E003",
+            ErrorKind::BadSubtraction => "E004",
+            ErrorKind::BadMultiplication => "E005",
+            ErrorKind::BadDivision => "E006",
+            ErrorKind::BadStringRepCount => "E007",
+            ErrorKind::BadComparison => "E008",
+            ErrorKind::UndefinedVariable(_) => "E009",
+            ErrorKind::NotCallable => "E010",
+            ErrorKind::ArityMismatch { .. } => "E011",
+            ErrorKind::UndefinedProperty(_) => "E012",
+            ErrorKind::OnlyInstancesHaveProperties => "E013",
+            ErrorKind::SuperclassMustBeClass => "E014",
+            ErrorKind::NativeFunctionError => "E015",
+            ErrorKind::StackOverflow => "E016",
+            ErrorKind::InvalidBytecode => "E900",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ErrorKind::BadNumericalNegation => write!(f, "Bad Numerical Negation"),
+            ErrorKind::BadAddition => write!(f, "Bad Addition"),
+            ErrorKind::BadSubtraction => write!(f, "Bad Subtraction"),
+            ErrorKind::BadMultiplication => write!(f, "Bad Multiplication"),
+            ErrorKind::BadDivision => write!(f, "Bad Division"),
+            ErrorKind::BadStringRepCount => {
+                write!(f, "Bad count for string repitition, expected an integer")
+            }
+            ErrorKind::BadComparison => write!(f, "Bad Comparison"),
+            ErrorKind::UndefinedVariable(name) => write!(f, "Undefined variable `{}`", name),
+            ErrorKind::NotCallable => write!(f, "Value is not callable"),
+            ErrorKind::ArityMismatch { expected, found } => {
+                write!(f, "Expected {} arguments but got {}", expected, found)
+            }
+            ErrorKind::UndefinedProperty(name) => write!(f, "Undefined property `{}`", name),
+            ErrorKind::OnlyInstancesHaveProperties => write!(f, "Only instances have properties"),
+            ErrorKind::SuperclassMustBeClass => write!(f, "Superclass must be a class"),
+            ErrorKind::NativeFunctionError => write!(f, "Error in native function"),
+            ErrorKind::StackOverflow => write!(f, "Stack overflow"),
+            ErrorKind::InvalidBytecode => write!(f, "Invalid bytecode"),
+        }
+    }
+}
@@ -0,0 +1,633 @@
+// Module declarations
+
+mod error;
+pub use error::{Error, ErrorKind};
+
+mod value;
+pub use value::{BoundMethod, Class, Closure, Instance, Upvalue, Value};
+
+// vm.rs
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::format_number;
+use crate::compile::{CompiledFunction, Constant, OpCode};
+use crate::interpreter::MAX_CALL_DEPTH;
+
+/// A function call in progress
+struct CallFrame {
+    closure: Rc<Closure>,
+    /// the next instruction to execute
+    ip: usize,
+    /// the stack index of slot zero of this call
+    slots: usize,
+}
+
+/// A stack-based virtual machine running bytecode produced by `Compiler`
+///
+/// Globals are kept between calls to `interpret` so that the VM can back a REPL.
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<String, Value>,
+    /// Upvalues still pointing into the stack, so closures created in the same scope share them
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+        }
+    }
+
+    pub fn define_global(&mut self, name: impl Into<String>, value: impl Into<Value>) {
+        self.globals.insert(name.into(), value.into());
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.get(name).cloned()
+    }
+
+    /// Run the top level code of a compiled program
+    pub fn interpret(&mut self, script: Rc<CompiledFunction>) -> Result<(), Error> {
+        let closure = Rc::new(Closure {
+            function: script,
+            upvalues: Vec::new(),
+        });
+        self.stack.push(Value::Closure(closure.clone()));
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: 0,
+        });
+
+        let result = self.run();
+        if result.is_err() {
+            // unwind everything, only the globals survive a runtime error
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    fn frame(&self) -> &CallFrame {
+        // unwrap: only called while running, when there is always at least the script's frame
+        self.frames.last().unwrap()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frames.last_mut().unwrap();
+        let byte = frame.closure.function.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let frame = self.frames.last_mut().unwrap();
+        let operand = frame.closure.function.chunk.read_u16(frame.ip);
+        frame.ip += 2;
+        operand
+    }
+
+    fn read_constant(&mut self) -> Constant {
+        let index = self.read_u16() as usize;
+        self.frame().closure.function.chunk.constants[index].clone()
+    }
+
+    fn read_string(&mut self) -> Result<Rc<str>, Error> {
+        match self.read_constant() {
+            Constant::String(name) => Ok(name),
+            constant => Err(self.error(
+                format!("expected a name but found `{}`", constant),
+                ErrorKind::InvalidBytecode,
+            )),
+        }
+    }
+
+    /// A runtime error located at the instruction being executed
+    fn error(&self, msg: impl Into<String>, kind: ErrorKind) -> Error {
+        let frame = self.frame();
+        let span = frame
+            .closure
+            .function
+            .chunk
+            .span_at(frame.ip.saturating_sub(1));
+        Error::spanned(msg, span, kind)
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        // unwrap: the compiler never emits code that pops more than it pushed
+        self.stack.pop().unwrap()
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn run(&mut self) -> Result<(), Error> {
+        loop {
+            let byte = self.read_byte();
+            let op = match OpCode::from_byte(byte) {
+                Some(op) => op,
+                None => {
+                    return Err(self.error(
+                        format!("unknown opcode {}", byte),
+                        ErrorKind::InvalidBytecode,
+                    ))
+                }
+            };
+
+            match op {
+                OpCode::CONSTANT => {
+                    let constant = self.read_constant();
+                    self.push(Value::from(&constant));
+                }
+                OpCode::NIL => self.push(Value::Nil),
+                OpCode::TRUE => self.push(Value::Bool(true)),
+                OpCode::FALSE => self.push(Value::Bool(false)),
+                OpCode::POP => {
+                    self.pop();
+                }
+                OpCode::GET_LOCAL => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.push(self.stack[slot].clone());
+                }
+                OpCode::SET_LOCAL => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.stack[slot] = self.peek(0).clone();
+                }
+                OpCode::GET_GLOBAL => {
+                    let name = self.read_string()?;
+                    match self.globals.get(&*name) {
+                        Some(value) => self.push(value.clone()),
+                        None => {
+                            return Err(
+                                self.error("", ErrorKind::UndefinedVariable(name.to_string()))
+                            )
+                        }
+                    }
+                }
+                OpCode::DEFINE_GLOBAL => {
+                    let name = self.read_string()?;
+                    let value = self.pop();
+                    self.globals.insert(name.to_string(), value);
+                }
+                OpCode::SET_GLOBAL => {
+                    let name = self.read_string()?;
+                    let value = self.peek(0).clone();
+                    match self.globals.get_mut(&*name) {
+                        Some(global) => *global = value,
+                        None => {
+                            return Err(
+                                self.error("", ErrorKind::UndefinedVariable(name.to_string()))
+                            )
+                        }
+                    }
+                }
+                OpCode::GET_UPVALUE => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = self.frame().closure.upvalues[index].clone();
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.push(value);
+                }
+                OpCode::SET_UPVALUE => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = self.frame().closure.upvalues[index].clone();
+                    let value = self.peek(0).clone();
+                    match &mut *upvalue.borrow_mut() {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    };
+                }
+                OpCode::GET_PROPERTY => {
+                    let name = self.read_string()?;
+                    let instance = match self.peek(0) {
+                        Value::Instance(instance) => instance.clone(),
+                        _ => return Err(self.error("", ErrorKind::OnlyInstancesHaveProperties)),
+                    };
+                    let field = instance.fields.borrow().get(&*name).cloned();
+                    let value = match field {
+                        Some(value) => value,
+                        None => self.bind_method(&instance.class, &name)?,
+                    };
+                    self.pop();
+                    self.push(value);
+                }
+                OpCode::SET_PROPERTY => {
+                    let name = self.read_string()?;
+                    let value = self.pop();
+                    match self.pop() {
+                        Value::Instance(instance) => {
+                            instance
+                                .fields
+                                .borrow_mut()
+                                .insert(name.to_string(), value.clone());
+                        }
+                        _ => return Err(self.error("", ErrorKind::OnlyInstancesHaveProperties)),
+                    }
+                    self.push(value);
+                }
+                OpCode::GET_SUPER => {
+                    let name = self.read_string()?;
+                    let superclass = match self.pop() {
+                        Value::Class(class) => class,
+                        _ => return Err(self.error("", ErrorKind::SuperclassMustBeClass)),
+                    };
+                    let method = self.bind_method(&superclass, &name)?;
+                    self.pop();
+                    self.push(method);
+                }
+                OpCode::EQUAL => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.push(Value::Bool(left == right));
+                }
+                OpCode::NOT_EQUAL => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.push(Value::Bool(left != right));
+                }
+                OpCode::GREATER => self.comparison(|l, r| l > r)?,
+                OpCode::GREATER_EQUAL => self.comparison(|l, r| l >= r)?,
+                OpCode::LESS => self.comparison(|l, r| l < r)?,
+                OpCode::LESS_EQUAL => self.comparison(|l, r| l <= r)?,
+                OpCode::ADD => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let value = match (as_number(&left), as_number(&right), &left, &right) {
+                        (Some(l), Some(r), _, _) => Value::Number(l + r),
+                        // concatenate strings
+                        (_, _, Value::String(l), Value::String(r)) => {
+                            Value::String(format!("{}{}", l, r).into())
+                        }
+                        (_, _, Value::String(l), Value::Number(r)) => {
+                            Value::String(format!("{}{}", l, format_number(*r)).into())
+                        }
+                        (_, _, Value::Number(l), Value::String(r)) => {
+                            Value::String(format!("{}{}", format_number(*l), r).into())
+                        }
+                        _ => return Err(self.error("", ErrorKind::BadAddition)),
+                    };
+                    self.push(value);
+                }
+                OpCode::SUBTRACT => self.arithmetic(|l, r| l - r, ErrorKind::BadSubtraction)?,
+                OpCode::MULTIPLY => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let value = match (as_number(&left), as_number(&right), &left, &right) {
+                        (Some(l), Some(r), _, _) => Value::Number(l * r),
+                        // string repitition
+                        (_, _, Value::String(l), Value::Number(r)) => {
+                            if r.fract() != 0.0 {
+                                return Err(self.error("", ErrorKind::BadStringRepCount));
+                            }
+                            Value::String(l.repeat(r.trunc() as usize).into())
+                        }
+                        _ => return Err(self.error("", ErrorKind::BadMultiplication)),
+                    };
+                    self.push(value);
+                }
+                OpCode::DIVIDE => self.arithmetic(|l, r| l / r, ErrorKind::BadDivision)?,
+                OpCode::NOT => {
+                    let value = self.pop();
+                    self.push(Value::Bool(!value.is_truthy()));
+                }
+                OpCode::NEGATE => match self.pop() {
+                    Value::Number(n) => self.push(Value::Number(-n)),
+                    _ => return Err(self.error("", ErrorKind::BadNumericalNegation)),
+                },
+                OpCode::PRINT => {
+                    let value = self.pop();
+                    println!("{}", value);
+                }
+                OpCode::STRINGIFY => {
+                    let value = self.pop();
+                    self.push(Value::String(value.to_string().into()));
+                }
+                OpCode::JUMP => {
+                    let offset = self.read_u16() as usize;
+                    self.frames.last_mut().unwrap().ip += offset;
+                }
+                OpCode::JUMP_IF_FALSE => {
+                    let offset = self.read_u16() as usize;
+                    if !self.peek(0).is_truthy() {
+                        self.frames.last_mut().unwrap().ip += offset;
+                    }
+                }
+                OpCode::LOOP => {
+                    let offset = self.read_u16() as usize;
+                    self.frames.last_mut().unwrap().ip -= offset;
+                }
+                OpCode::CALL => {
+                    let arg_count = self.read_byte() as usize;
+                    self.call_value(arg_count)?;
+                }
+                OpCode::CLOSURE => {
+                    let function = match self.read_constant() {
+                        Constant::Function(function) => function,
+                        constant => {
+                            return Err(self.error(
+                                format!("expected a function but found `{}`", constant),
+                                ErrorKind::InvalidBytecode,
+                            ))
+                        }
+                    };
+                    let mut upvalues = Vec::with_capacity(function.upvalue_count);
+                    for _ in 0..function.upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        if is_local {
+                            let slot = self.frame().slots + index;
+                            upvalues.push(self.capture_upvalue(slot));
+                        } else {
+                            upvalues.push(self.frame().closure.upvalues[index].clone());
+                        }
+                    }
+                    self.push(Value::Closure(Rc::new(Closure { function, upvalues })));
+                }
+                OpCode::CLOSE_UPVALUE => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::RETURN => {
+                    let result = self.pop();
+                    // unwrap: RETURN is only executed inside a frame
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
+                    if self.frames.is_empty() {
+                        return Ok(());
+                    }
+                    self.push(result);
+                }
+                OpCode::CLASS => {
+                    let name = self.read_string()?;
+                    self.push(Value::Class(Rc::new(Class::new(name.to_string()))));
+                }
+                OpCode::INHERIT => {
+                    let superclass = match self.peek(1) {
+                        Value::Class(class) => class.clone(),
+                        _ => return Err(self.error("", ErrorKind::SuperclassMustBeClass)),
+                    };
+                    if let Value::Class(subclass) = self.pop() {
+                        // copy the methods down, methods defined by the subclass overwrite them later
+                        subclass
+                            .methods
+                            .borrow_mut()
+                            .extend(superclass.methods.borrow().clone());
+                    }
+                }
+                OpCode::METHOD => {
+                    let name = self.read_string()?;
+                    if let Value::Closure(method) = self.pop() {
+                        if let Value::Class(class) = self.peek(0) {
+                            class.methods.borrow_mut().insert(name.to_string(), method);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Pop two operands and push the result of a numerical operation on them
+    fn arithmetic(&mut self, operation: fn(f64, f64) -> f64, kind: ErrorKind) -> Result<(), Error> {
+        let right = self.pop();
+        let left = self.pop();
+        match (as_number(&left), as_number(&right)) {
+            (Some(l), Some(r)) => {
+                self.push(Value::Number(operation(l, r)));
+                Ok(())
+            }
+            _ => Err(self.error("", kind)),
+        }
+    }
+
+    fn comparison(&mut self, operation: fn(f64, f64) -> bool) -> Result<(), Error> {
+        let right = self.pop();
+        let left = self.pop();
+        match (as_number(&left), as_number(&right)) {
+            (Some(l), Some(r)) => {
+                self.push(Value::Bool(operation(l, r)));
+                Ok(())
+            }
+            _ => Err(self.error("", ErrorKind::BadComparison)),
+        }
+    }
+
+    /// Call the value sitting below `arg_count` arguments on the stack
+    fn call_value(&mut self, arg_count: usize) -> Result<(), Error> {
+        let callee_slot = self.stack.len() - arg_count - 1;
+        match self.stack[callee_slot].clone() {
+            Value::Closure(closure) => self.call(closure, arg_count),
+            Value::BoundMethod(bound) => {
+                self.stack[callee_slot] = bound.receiver.clone();
+                self.call(bound.method.clone(), arg_count)
+            }
+            Value::Class(class) => {
+                self.stack[callee_slot] = Value::Instance(Rc::new(Instance::new(class.clone())));
+                let initializer = class.methods.borrow().get("init").cloned();
+                match initializer {
+                    Some(initializer) => self.call(initializer, arg_count),
+                    None if arg_count != 0 => Err(self.error(
+                        "",
+                        ErrorKind::ArityMismatch {
+                            expected: 0,
+                            found: arg_count,
+                        },
+                    )),
+                    None => Ok(()),
+                }
+            }
+            Value::NativeFunction(function) => {
+                if function.arity() != arg_count {
+                    return Err(self.error(
+                        "",
+                        ErrorKind::ArityMismatch {
+                            expected: function.arity(),
+                            found: arg_count,
+                        },
+                    ));
+                }
+                let mut arguments = Vec::with_capacity(arg_count);
+                for argument in &self.stack[callee_slot + 1..] {
+                    match argument.to_evaluate() {
+                        Some(argument) => arguments.push(argument),
+                        None => {
+                            return Err(self.error(
+                                format!("can't pass `{}` to {}", argument, function),
+                                ErrorKind::NativeFunctionError,
+                            ))
+                        }
+                    }
+                }
+                let result = function
+                    .call(arguments)
+                    .map_err(|message| self.error(message, ErrorKind::NativeFunctionError))?;
+                self.stack.truncate(callee_slot);
+                self.push(result.into());
+                Ok(())
+            }
+            _ => Err(self.error("", ErrorKind::NotCallable)),
+        }
+    }
+
+    fn call(&mut self, closure: Rc<Closure>, arg_count: usize) -> Result<(), Error> {
+        if closure.function.arity != arg_count {
+            return Err(self.error(
+                "",
+                ErrorKind::ArityMismatch {
+                    expected: closure.function.arity,
+                    found: arg_count,
+                },
+            ));
+        }
+        // the script has a frame too but it isn't a call
+        if self.frames.len() > MAX_CALL_DEPTH {
+            return Err(self.error("", ErrorKind::StackOverflow));
+        }
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: self.stack.len() - arg_count - 1,
+        });
+        Ok(())
+    }
+
+    /// Look up a method on a class and bind it to the instance on top of the stack
+    fn bind_method(&self, class: &Class, name: &str) -> Result<Value, Error> {
+        match class.methods.borrow().get(name) {
+            Some(method) => Ok(Value::BoundMethod(Rc::new(BoundMethod {
+                receiver: self.peek(0).clone(),
+                method: method.clone(),
+            }))),
+            None => Err(self.error("", ErrorKind::UndefinedProperty(name.to_string()))),
+        }
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(&*upvalue.borrow(), Upvalue::Open(open) if *open == slot));
+        if let Some(upvalue) = existing {
+            return upvalue.clone();
+        }
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    /// Move every captured variable at or above `slot` off the stack
+    fn close_upvalues(&mut self, slot: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let open = match &*upvalue.borrow() {
+                Upvalue::Open(open) => *open,
+                Upvalue::Closed(_) => return false,
+            };
+            if open >= slot {
+                *upvalue.borrow_mut() = Upvalue::Closed(stack[open].clone());
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Vm::new()
+    }
+}
+
+/// Booleans are treated as 0 or 1 in arithmetic, like the evaluator does
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => Some(*n),
+        Value::Bool(true) => Some(1.0),
+        Value::Bool(false) => Some(0.0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ErrorKind, Value, Vm};
+    use crate::{ast::Stmt, compile::Compiler, Lexer};
+
+    fn run(vm: &mut Vm, source: &str) -> Result<(), super::Error> {
+        let statements = Lexer::new(source)
+            .advance_to_parsing()
+            .collect::<Result<Vec<Stmt>, _>>()
+            .unwrap();
+        vm.interpret(Compiler::compile(&statements).unwrap())
+    }
+
+    #[test]
+    fn test_closures_share_captured_variables() -> Result<(), super::Error> {
+        let mut vm = Vm::new();
+        run(
+            &mut vm,
+            r#"
+            var get;
+            var set;
+            {
+                var a = 1;
+                fun g() { return a; }
+                fun s(value) { a = value; }
+                get = g;
+                set = s;
+            }
+            set(5);
+            var result = get();
+            "#,
+        )?;
+        assert_eq!(Some(Value::Number(5.)), vm.get_global("result"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_loops_break_and_continue() -> Result<(), super::Error> {
+        let mut vm = Vm::new();
+        run(
+            &mut vm,
+            r#"
+            var sum = 0;
+            for (var i = 0; i < 10; i = i + 1) {
+                var skip = i == 3;
+                if (skip) continue;
+                if (i == 6) break;
+                sum = sum + i;
+            }
+            "#,
+        )?;
+        assert_eq!(Some(Value::Number(12.)), vm.get_global("sum"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_runtime_error_keeps_globals() {
+        let mut vm = Vm::new();
+        let result = run(&mut vm, "var a = 1; var b = a + nil;");
+        assert!(matches!(
+            result.map_err(|e| e.kind().clone()),
+            Err(ErrorKind::BadAddition)
+        ));
+        assert_eq!(Some(Value::Number(1.)), vm.get_global("a"));
+    }
+}
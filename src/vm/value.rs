@@ -0,0 +1,165 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::format_number;
+use crate::compile::{CompiledFunction, Constant};
+use crate::evaluate::{self, NativeFunction};
+
+/// A value on the VM's stack
+///
+/// Values from the tree-walking evaluator that the VM has no representation for
+/// (lists and native handles) are carried around untouched as `Foreign`.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Number(f64),
+    String(Rc<str>),
+    Bool(bool),
+    Nil,
+    Closure(Rc<Closure>),
+    NativeFunction(Rc<NativeFunction>),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+    BoundMethod(Rc<BoundMethod>),
+    Foreign(evaluate::Value),
+}
+
+impl Value {
+    /// `false` and `nil` are falsey, everything else is truthy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Bool(false) | Value::Nil)
+    }
+
+    /// Convert back into a tree-walker value, closures and classes compiled to bytecode can't be
+    pub fn to_evaluate(&self) -> Option<evaluate::Value> {
+        match self {
+            Value::Number(n) => Some(evaluate::Value::Number(*n)),
+            Value::String(s) => Some(evaluate::Value::String(s.to_string())),
+            Value::Bool(b) => Some(evaluate::Value::Bool(*b)),
+            Value::Nil => Some(evaluate::Value::Nil),
+            Value::NativeFunction(function) => {
+                Some(evaluate::Value::NativeFunction(function.clone()))
+            }
+            Value::Foreign(value) => Some(value.clone()),
+            Value::Closure(_) | Value::Class(_) | Value::Instance(_) | Value::BoundMethod(_) => {
+                None
+            }
+        }
+    }
+}
+
+impl From<evaluate::Value> for Value {
+    fn from(value: evaluate::Value) -> Self {
+        match value {
+            evaluate::Value::Number(n) => Value::Number(n),
+            evaluate::Value::String(s) => Value::String(s.into()),
+            evaluate::Value::Bool(b) => Value::Bool(b),
+            evaluate::Value::Nil => Value::Nil,
+            evaluate::Value::NativeFunction(function) => Value::NativeFunction(function),
+            value => Value::Foreign(value),
+        }
+    }
+}
+
+impl From<&Constant> for Value {
+    fn from(constant: &Constant) -> Self {
+        match constant {
+            Constant::Number(n) => Value::Number(*n),
+            Constant::String(s) => Value::String(s.clone()),
+            // functions are only ever loaded by CLOSURE
+            Constant::Function(function) => Value::Closure(Rc::new(Closure {
+                function: function.clone(),
+                upvalues: Vec::new(),
+            })),
+        }
+    }
+}
+
+/// Primitives are compared by value and objects by identity, same as the evaluator
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(l), Value::Number(r)) => l == r,
+            (Value::String(l), Value::String(r)) => l == r,
+            (Value::Bool(l), Value::Bool(r)) => l == r,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Closure(l), Value::Closure(r)) => Rc::ptr_eq(l, r),
+            (Value::NativeFunction(l), Value::NativeFunction(r)) => Rc::ptr_eq(l, r),
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+            (Value::BoundMethod(l), Value::BoundMethod(r)) => Rc::ptr_eq(l, r),
+            (Value::Foreign(l), Value::Foreign(r)) => l == r,
+            _ => false,
+        }
+    }
+}
+
+/// Formats values the way `print` shows them, matching the evaluator
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", format_number(*n)),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Bool(true) => write!(f, "true"),
+            Value::Bool(false) => write!(f, "false"),
+            Value::Nil => write!(f, "nil"),
+            Value::Closure(closure) => write!(f, "{}", closure.function),
+            Value::NativeFunction(function) => write!(f, "{}", function),
+            Value::Class(class) => write!(f, "{}", class.name),
+            Value::Instance(instance) => write!(f, "{} instance", instance.class.name),
+            Value::BoundMethod(bound) => write!(f, "{}", bound.method.function),
+            Value::Foreign(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// A compiled function together with the variables it captured
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+/// A captured variable, it points into the stack until the variable goes out of scope
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub methods: RefCell<HashMap<String, Rc<Closure>>>,
+}
+
+impl Class {
+    pub fn new(name: impl Into<String>) -> Self {
+        Class {
+            name: name.into(),
+            methods: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: RefCell<HashMap<String, Value>>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Self {
+        Instance {
+            class,
+            fields: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+/// A method looked up on an instance, remembering the instance to use as `this`
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}